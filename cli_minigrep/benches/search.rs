@@ -0,0 +1,61 @@
+extern crate criterion;
+extern crate minigrep;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use minigrep::{search_lines, Config};
+
+// corpus generates size bytes of log-like lines from a fixed seed, so every
+// run searches the same text.
+fn corpus(size: usize) -> String {
+    const WORDS: &[&str] = &[
+        "the", "request", "user", "timeout", "connection", "error", "ok", "GET", "POST", "/api/v1/items",
+        "retry", "db", "cache", "miss", "hit", "latency", "ms", "status", "200", "500", "worker", "queue",
+    ];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut text = String::with_capacity(size + 128);
+    while text.len() < size {
+        text.push_str(&format!("2026-10-{:02} ", next() % 28 + 1));
+        for _ in 0..next() % 12 + 4 {
+            text.push_str(WORDS[(next() % WORDS.len() as u64) as usize]);
+            text.push(' ');
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// lines_contains is how plain queries were searched before the literal
+// finder: split into lines, then look through each one.
+fn lines_contains<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|line| line.contains(query)).collect()
+}
+
+fn bench_literal(c: &mut Criterion) {
+    for &size in &[1 << 20, 16 << 20] {
+        let text = corpus(size);
+        let mut group = c.benchmark_group(format!("literal/{}MiB", size >> 20));
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.sample_size(20);
+        // A needle that's never there, one that's on a few lines, and one
+        // that's on most of them.
+        for &query in &["zebracorn", "status 500 worker", "request"] {
+            let cfg = Config { query: query.to_string(), case_sensitive: true, ..Default::default() };
+            group.bench_with_input(BenchmarkId::new("lines_contains", query), &text, |b, text| {
+                b.iter(|| lines_contains(query, text).len())
+            });
+            group.bench_with_input(BenchmarkId::new("search_lines", query), &text, |b, text| {
+                b.iter(|| search_lines(&cfg, text).len())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_literal);
+criterion_main!(benches);
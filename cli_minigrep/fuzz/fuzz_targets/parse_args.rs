@@ -0,0 +1,41 @@
+// Feeds the command line parser arguments separated by NUL bytes, with no
+// config files or environment to read. Parsing can fail, but mustn't panic,
+// and whatever it accepts has to read back the same from what --print-config
+// prints for it.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate minigrep;
+
+use std::env;
+use std::fs;
+use std::process;
+use minigrep::Config;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut args = vec!["minigrep".to_string()];
+    args.extend(text.split('\0').map(String::from));
+    let cfg = match Config::from_sources(args, &[]) {
+        Ok(cfg) => cfg,
+        Err(_) => return,
+    };
+    let rc = env::temp_dir().join(format!("minigrep-fuzz-{}", process::id()));
+    fs::write(&rc, cfg.to_file()).unwrap();
+    let args = vec!["minigrep".to_string(), "--print-config".to_string(), "--".to_string(), cfg.query.clone(), cfg.path.clone()];
+    let again = Config::from_sources(args, &[rc.clone()]);
+    let again = again.unwrap_or_else(|err| panic!("{:?} doesn't read back: {}", cfg.to_file(), err));
+    // Times like 2h are read against the clock, which moves on between the
+    // two reads; the options still hold what was written for them.
+    let settled = |cfg: Config| Config {
+        print_config: true,
+        sources: Vec::new(),
+        newer_than: None,
+        older_than: None,
+        since: None,
+        until: None,
+        ..cfg
+    };
+    let (cfg, again) = (format!("{:?}", settled(cfg)), format!("{:?}", settled(again)));
+    assert!(cfg == again, "read back differently:\n{}\n{}", cfg, again);
+});
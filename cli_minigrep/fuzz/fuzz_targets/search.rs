@@ -0,0 +1,34 @@
+// Searches text for a query, both taken from the input: the query is
+// everything up to the first newline. The matcher mustn't panic, and its
+// results have to agree with the obvious line by line search.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate minigrep;
+
+use minigrep::{find_all, search, search_case_insensitive, search_lines, Config};
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let (query, contents) = match text.find('\n') {
+        Some(ii) => (&text[..ii], &text[ii + 1..]),
+        None => return,
+    };
+
+    let naive: Vec<&str> = contents.lines().filter(|line| line.contains(query)).collect();
+    let found = search(query, contents);
+    assert_eq!(naive, found);
+    let insensitive = search_case_insensitive(query, contents);
+    assert!(insensitive.len() <= contents.lines().count());
+
+    for &case_sensitive in &[true, false] {
+        let cfg = Config { query: query.to_string(), case_sensitive, ..Default::default() };
+        let lines: Vec<&str> = contents.lines().collect();
+        for m in search_lines(&cfg, contents) {
+            assert_eq!(lines[m.line_number - 1], m.line);
+        }
+        for (start, end) in find_all(query, contents, case_sensitive) {
+            assert!(start <= end && contents.is_char_boundary(start) && contents.is_char_boundary(end));
+        }
+    }
+});
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+// Masks says which kinds of variable text normalize replaces, so lines that
+// differ only in them are counted together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Masks {
+    pub timestamps: bool,
+    pub uuids: bool,
+    pub hex: bool,
+    pub numbers: bool,
+}
+
+impl Masks {
+    // "numbers,uuids" -> Masks { numbers: true, uuids: true, .. }
+    // "all" -> every mask
+    pub fn parse(s: &str) -> Result<Masks, String> {
+        let mut masks = Masks::default();
+        for name in s.split(',').map(|n| n.trim()) {
+            match name {
+                "timestamps" => masks.timestamps = true,
+                "uuids" => masks.uuids = true,
+                "hex" => masks.hex = true,
+                "numbers" => masks.numbers = true,
+                "all" => masks = Masks { timestamps: true, uuids: true, hex: true, numbers: true },
+                _ => return Err(format!("mask {} should be timestamps, uuids, hex, numbers or all", name)),
+            }
+        }
+        Ok(masks)
+    }
+
+    // union is every mask set in either self or other.
+    pub fn union(self, other: Masks) -> Masks {
+        Masks {
+            timestamps: self.timestamps || other.timestamps,
+            uuids: self.uuids || other.uuids,
+            hex: self.hex || other.hex,
+            numbers: self.numbers || other.numbers,
+        }
+    }
+}
+
+// normalize replaces the parts of line picked out by masks with a
+// placeholder: <TS>, <UUID>, <HEX> or <N>. Where a span could be more than
+// one kind, the first of those listed wins, so the digits of a timestamp
+// aren't masked as numbers.
+// "took 35ms at 2026-10-01T13:30:00Z" -> "took <N>ms at <TS>"
+pub fn normalize(line: &str, masks: &Masks) -> String {
+    let b = line.as_bytes();
+    let mut out = String::with_capacity(line.len());
+    let mut ii = 0;
+    while ii < b.len() {
+        let boundary = ii == 0 || !is_word(b[ii - 1]);
+        let rules: [(bool, Rule, &str); 4] = [
+            (masks.timestamps && boundary, timestamp, "<TS>"),
+            (masks.uuids && boundary, uuid, "<UUID>"),
+            (masks.hex && boundary, hex, "<HEX>"),
+            (masks.numbers, number, "<N>"),
+        ];
+        let found = rules.iter()
+            .filter(|&&(on, _, _)| on)
+            .find_map(|&(_, rule, placeholder)| rule(b, ii).map(|end| (end, placeholder)));
+        match found {
+            Some((end, placeholder)) => {
+                out.push_str(placeholder);
+                ii = end;
+            },
+            None => {
+                let c = line[ii..].chars().next().unwrap_or_default();
+                out.push(c);
+                ii += c.len_utf8();
+            },
+        }
+    }
+    out
+}
+
+// Rule finds where the text it masks ends, if it starts at the given index.
+type Rule = fn(&[u8], usize) -> Option<usize>;
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+// ends_word reports whether a match ending at end isn't part of a longer
+// word.
+fn ends_word(b: &[u8], end: usize) -> bool {
+    b.get(end).is_none_or(|&c| !is_word(c))
+}
+
+// digits counts the ASCII digits starting at ii.
+fn digits(b: &[u8], ii: usize) -> usize {
+    b[ii.min(b.len())..].iter().take_while(|c| c.is_ascii_digit()).count()
+}
+
+// fixed checks that b has a run of exactly n digits at ii.
+fn fixed(b: &[u8], ii: usize, n: usize) -> bool {
+    digits(b, ii) == n
+}
+
+// timestamp matches a date and time at ii, returning where it ends:
+// ISO 8601 "2026-10-01", "2026-10-01T13:30:00.123+02:00", a bare time of day
+// "13:30:00", or syslog's "Oct  1 13:30:00".
+fn timestamp(b: &[u8], ii: usize) -> Option<usize> {
+    let mut end = if fixed(b, ii, 4) && b.get(ii + 4) == Some(&b'-') && fixed(b, ii + 5, 2)
+        && b.get(ii + 7) == Some(&b'-') && fixed(b, ii + 8, 2) {
+        let date = ii + 10;
+        match matches!(b.get(date), Some(b'T') | Some(b' ')) {
+            true => time(b, date + 1).unwrap_or(date),
+            false => date,
+        }
+    } else if let Some(end) = time(b, ii) {
+        end
+    } else {
+        let month = MONTHS.iter().any(|m| b[ii..].starts_with(m.as_bytes()));
+        let mut jj = ii + 3;
+        while month && b.get(jj) == Some(&b' ') {
+            jj += 1;
+        }
+        let day = digits(b, jj);
+        if !month || jj == ii + 3 || day == 0 || day > 2 || b.get(jj + day) != Some(&b' ') {
+            return None;
+        }
+        time(b, jj + day + 1)?
+    };
+    // Fractions of a second and a zone.
+    if b.get(end) == Some(&b'.') && digits(b, end + 1) > 0 {
+        end += 1 + digits(b, end + 1);
+    }
+    if b.get(end) == Some(&b'Z') {
+        end += 1;
+    } else if matches!(b.get(end), Some(b'+') | Some(b'-')) && fixed(b, end + 1, 2) {
+        let colon = (b.get(end + 3) == Some(&b':')) as usize;
+        if fixed(b, end + 3 + colon, 2) {
+            end += 5 + colon;
+        }
+    }
+    match ends_word(b, end) {
+        true => Some(end),
+        false => None,
+    }
+}
+
+pub const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// time matches HH:MM:SS at ii.
+fn time(b: &[u8], ii: usize) -> Option<usize> {
+    let ok = fixed(b, ii, 2) && b.get(ii + 2) == Some(&b':') && fixed(b, ii + 3, 2)
+        && b.get(ii + 5) == Some(&b':') && fixed(b, ii + 6, 2);
+    match ok {
+        true => Some(ii + 8),
+        false => None,
+    }
+}
+
+// uuid matches 8-4-4-4-12 hex digits at ii.
+fn uuid(b: &[u8], ii: usize) -> Option<usize> {
+    let mut jj = ii;
+    for (n, &len) in [8, 4, 4, 4, 12].iter().enumerate() {
+        if n > 0 {
+            if b.get(jj) != Some(&b'-') {
+                return None;
+            }
+            jj += 1;
+        }
+        if b[jj.min(b.len())..].iter().take_while(|c| c.is_ascii_hexdigit()).count() < len {
+            return None;
+        }
+        jj += len;
+    }
+    match ends_word(b, jj) {
+        true => Some(jj),
+        false => None,
+    }
+}
+
+// hex matches 0x followed by hex digits, or a word of 8 or more hex digits
+// with at least one digit in it, so plain words like "deadbeef" and
+// "facade" are left alone.
+fn hex(b: &[u8], ii: usize) -> Option<usize> {
+    let is_hex = |c: &&u8| c.is_ascii_hexdigit();
+    if b[ii..].starts_with(b"0x") || b[ii..].starts_with(b"0X") {
+        let n = b[ii + 2..].iter().take_while(is_hex).count();
+        if n > 0 && ends_word(b, ii + 2 + n) {
+            return Some(ii + 2 + n);
+        }
+    }
+    let n = b[ii..].iter().take_while(is_hex).count();
+    let has_digit = b[ii..ii + n].iter().any(|c| c.is_ascii_digit());
+    match n >= 8 && has_digit && ends_word(b, ii + n) {
+        true => Some(ii + n),
+        false => None,
+    }
+}
+
+// number matches a run of digits at ii, with a fraction if it has one.
+// Numbers can end a word, as in "user42", so they don't need a boundary.
+fn number(b: &[u8], ii: usize) -> Option<usize> {
+    let n = digits(b, ii);
+    if n == 0 {
+        return None;
+    }
+    let mut end = ii + n;
+    if b.get(end) == Some(&b'.') && digits(b, end + 1) > 0 {
+        end += 1 + digits(b, end + 1);
+    }
+    Some(end)
+}
+
+// Aggregate counts how often each distinct line turns up, in one pass over
+// the lines it's given.
+#[derive(Default)]
+pub struct Aggregate {
+    // counts maps each line to its count and when it was first seen, so ties
+    // are listed in the order they turned up.
+    counts: HashMap<String, (usize, usize)>,
+}
+
+impl Aggregate {
+    pub fn add(&mut self, line: String) {
+        let seen = self.counts.len();
+        self.counts.entry(line).or_insert((0, seen)).0 += 1;
+    }
+
+    // top lists the n most common lines with their counts, most common
+    // first.
+    pub fn top(&self, n: usize) -> Vec<(usize, &str)> {
+        let mut all: Vec<(&String, &(usize, usize))> = self.counts.iter().collect();
+        all.sort_by_key(|&(_, &(count, seen))| (std::cmp::Reverse(count), seen));
+        all.into_iter().take(n).map(|(line, &(count, _))| (count, line.as_str())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> Masks {
+        Masks::parse("all").unwrap()
+    }
+
+    #[test]
+    fn parse_masks() {
+        assert_eq!(Masks { numbers: true, uuids: true, ..Default::default() }, Masks::parse("numbers, uuids").unwrap());
+        assert!(Masks::parse("ips").is_err());
+    }
+
+    #[test]
+    fn masks_variable_text() {
+        assert_eq!("took <N>ms at <TS>", normalize("took 35ms at 2026-10-01T13:30:00Z", &all()));
+        assert_eq!("<TS> host sshd[<N>]: ok", normalize("Oct  1 13:30:00 host sshd[4121]: ok", &all()));
+        assert_eq!("<TS>|<TS>|<TS>", normalize("2026-10-01|13:30:00.5|2026-10-01 13:30:00+02:00", &all()));
+        assert_eq!("req <UUID> user<N>", normalize("req 123e4567-e89b-12d3-a456-426614174000 user42", &all()));
+        assert_eq!("addr <HEX> id <HEX> deadbeef", normalize("addr 0x7ffe id 9f86d081884c deadbeef", &all()));
+        assert_eq!("v<N> is <N> — ünïcode", normalize("v1.2 is 3 — ünïcode", &all()));
+
+        let numbers = Masks::parse("numbers").unwrap();
+        assert_eq!("<N>-<N>-<N> id <N>f<N>d<N>c", normalize("2026-10-01 id 9f86d081884c", &numbers));
+        assert_eq!("unchanged 42", normalize("unchanged 42", &Masks::default()));
+    }
+
+    #[test]
+    fn counts_distinct_lines() {
+        let mut agg = Aggregate::default();
+        for line in &["b", "a", "b", "c", "a", "b"] {
+            agg.add(line.to_string());
+        }
+        assert_eq!(vec![(3, "b"), (2, "a"), (1, "c")], agg.top(10));
+        assert_eq!(vec![(3, "b")], agg.top(1));
+    }
+}
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::prelude::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use flate2::read::GzDecoder;
+use filter;
+
+// Kind is the container format of an archive, worked out from its extension.
+#[derive(Debug, PartialEq)]
+pub enum Kind {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl Kind {
+    // archive.tar.gz -> Some(TarGz)
+    // notes.txt -> None
+    pub fn from_path(path: &str) -> Option<Kind> {
+        let path = path.to_lowercase();
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(Kind::TarGz)
+        } else if path.ends_with(".tar") {
+            Some(Kind::Tar)
+        } else if path.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+// Member is a single file stored inside an archive. Its contents are left
+// as bytes, so members that aren't text are skipped by whoever reads them,
+// the same way a binary file on disk is.
+pub struct Member {
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+// Keep decides from a member's size and modification time whether it's read,
+// so members the filters leave out never are.
+pub type Keep<'a> = dyn FnMut(u64, Option<SystemTime>) -> bool + 'a;
+
+// members reads every regular file out of the archive at path that keep
+// lets through.
+pub fn members(path: &str, kind: &Kind, keep: &mut Keep) -> Result<Vec<Member>, Box<dyn Error>> {
+    let f = File::open(path)?;
+    match *kind {
+        Kind::Tar => tar_members(f, keep),
+        Kind::TarGz => tar_members(GzDecoder::new(f), keep),
+        Kind::Zip => zip_members(f, keep),
+    }
+}
+
+fn tar_members<R: Read>(r: R, keep: &mut Keep) -> Result<Vec<Member>, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(r);
+    let mut members = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let modified = entry.header().mtime().ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        if !keep(entry.size(), modified) {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        members.push(Member { name, contents });
+    }
+    Ok(members)
+}
+
+fn zip_members<R: Read + Seek>(r: R, keep: &mut Keep) -> Result<Vec<Member>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(r)?;
+    let mut members = Vec::new();
+    for ii in 0..archive.len() {
+        let mut file = archive.by_index(ii)?;
+        if file.is_dir() || !keep(file.size(), Some(zip_time(file.last_modified()))) {
+            continue;
+        }
+        let name = file.name().to_string();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        members.push(Member { name, contents });
+    }
+    Ok(members)
+}
+
+// zip_time reads a zip member's modification time. Zip doesn't record the
+// time zone, so it's taken as UTC.
+fn zip_time(t: zip::DateTime) -> SystemTime {
+    let days = filter::days_from_civil(t.year() as i64, t.month() as i64, t.day() as i64);
+    let secs = days * 86400 + t.hour() as i64 * 3600 + t.minute() as i64 * 60 + t.second() as i64;
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_from_path() {
+        assert_eq!(Some(Kind::TarGz), Kind::from_path("build.tar.gz"));
+        assert_eq!(Some(Kind::TarGz), Kind::from_path("build.TGZ"));
+        assert_eq!(Some(Kind::Tar), Kind::from_path("build.tar"));
+        assert_eq!(Some(Kind::Zip), Kind::from_path("build.zip"));
+        assert_eq!(None, Kind::from_path("poem.txt"));
+    }
+
+    #[test]
+    fn read_tar_members() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let contents = "Rust:\nsafe, fast, productive.\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "docs/rust.txt", contents.as_bytes()).unwrap();
+        let data = builder.into_inner().unwrap();
+
+        let members = tar_members(&data[..], &mut |_, _| true).unwrap();
+        assert_eq!(1, members.len());
+        assert_eq!("docs/rust.txt", members[0].name);
+        assert_eq!(contents.as_bytes(), &members[0].contents[..]);
+    }
+
+    #[test]
+    fn read_zip_members() {
+        let mut w = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        w.add_directory("docs/", Default::default()).unwrap();
+        w.start_file("docs/rust.txt", Default::default()).unwrap();
+        w.write_all(b"Pick three.\n").unwrap();
+        let data = w.finish().unwrap().into_inner();
+
+        let members = zip_members(std::io::Cursor::new(data.clone()), &mut |_, _| true).unwrap();
+        assert_eq!(1, members.len());
+        assert_eq!("docs/rust.txt", members[0].name);
+        assert_eq!(b"Pick three.\n", &members[0].contents[..]);
+
+        let mut seen = Vec::new();
+        let members = zip_members(std::io::Cursor::new(data), &mut |size, modified| {
+            seen.push((size, modified));
+            false
+        }).unwrap();
+        assert!(members.is_empty());
+        // Zip's earliest time, 1980-01-01 00:00, is the default.
+        assert_eq!(vec![(12, Some(UNIX_EPOCH + Duration::from_secs(315_532_800)))], seen);
+    }
+}
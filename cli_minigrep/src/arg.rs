@@ -9,8 +9,9 @@ impl Arg for String {
     type Error = String;
 
     // --some-flag=hello -> Result<"hello">
+    // --some-flag=a=b -> Result<"a=b">
     fn get_string(&self) -> Result<String, Self::Error> {
-        let parts: Vec<&str> = self.split("=").collect();
+        let parts: Vec<&str> = self.splitn(2, "=").collect();
         if parts.len() < 2 {
             return Ok("".to_string())
         }
@@ -23,6 +24,7 @@ impl Arg for String {
     // --some-flag=not-a-boolean -> Result<Self::Error>
     fn get_bool(&self) -> Result<bool, Self::Error> {
         match self.get_string() {
+            Ok(ref s) if s.is_empty() => Ok(true),
             Ok(s) => {
                 match s.parse() {
                     Ok(b) => Ok(b),
@@ -33,3 +35,28 @@ impl Arg for String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn bools_round_trip(name in "[a-z-]{1,12}", b: bool) {
+            prop_assert_eq!(Ok(b), format!("--{}={}", name, b).get_bool());
+            prop_assert_eq!(Ok(true), format!("--{}", name).get_bool());
+        }
+
+        #[test]
+        fn strings_round_trip(name in "[a-z-]{1,12}", value in ".*") {
+            prop_assert_eq!(Ok(value.clone()), format!("--{}={}", name, value).get_string());
+        }
+
+        #[test]
+        fn other_values_arent_bools(name in "[a-z-]{1,12}", value in ".+") {
+            prop_assume!(value != "true" && value != "false");
+            let arg = format!("--{}={}", name, value);
+            prop_assert!(arg.get_bool().is_err(), "{} read as a bool", arg);
+        }
+    }
+}
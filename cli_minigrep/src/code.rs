@@ -0,0 +1,233 @@
+use {find_all, Config, Match};
+
+// Kind is what a region of source code is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Code,
+    Comment,
+    // Str covers string and char literals, quotes included.
+    Str,
+}
+
+// Lang is the flavour of source code being read. Rust block comments nest,
+// and Rust has raw strings and lifetimes; everything else is read like C,
+// with backticks quoting strings as they do in Go and JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    Rust,
+    CLike,
+}
+
+impl Lang {
+    // src/main.rs -> Rust
+    // src/main.c -> CLike
+    pub fn from_path(path: &str) -> Lang {
+        match path.ends_with(".rs") {
+            true => Lang::Rust,
+            false => Lang::CLike,
+        }
+    }
+}
+
+// classify returns the kind of each byte of src. It only knows enough about
+// each language to find where comments and literals start and end, which is
+// all that's needed to tell them from code.
+pub fn classify(src: &str, lang: Lang) -> Vec<Kind> {
+    let b = src.as_bytes();
+    let mut kinds = vec![Kind::Code; b.len()];
+    let mut ii = 0;
+    while ii < b.len() {
+        let next = b.get(ii + 1).cloned();
+        let region = match b[ii] {
+            b'/' if next == Some(b'/') => Some((Kind::Comment, line_end(b, ii))),
+            b'/' if next == Some(b'*') => Some((Kind::Comment, block_end(b, ii, lang == Lang::Rust))),
+            b'"' => Some((Kind::Str, quoted_end(b, ii))),
+            b'`' if lang == Lang::CLike => Some((Kind::Str, quoted_end(b, ii))),
+            b'\'' => char_end(src, ii).map(|end| (Kind::Str, end)),
+            b'r' | b'b' if lang == Lang::Rust && !in_ident(b, ii) => raw_end(b, ii).map(|end| (Kind::Str, end)),
+            _ => None,
+        };
+        match region {
+            Some((kind, end)) => {
+                for k in &mut kinds[ii..end] {
+                    *k = kind;
+                }
+                ii = end;
+            },
+            None => ii += 1,
+        }
+    }
+    kinds
+}
+
+// line_end finds the end of a // comment, leaving the line break as code.
+fn line_end(b: &[u8], start: usize) -> usize {
+    b[start..].iter().position(|&c| c == b'\n').map_or(b.len(), |n| start + n)
+}
+
+// block_end finds the end of a /* comment */, counting nested comments when
+// they nest.
+fn block_end(b: &[u8], start: usize, nests: bool) -> usize {
+    let mut depth = 0;
+    let mut ii = start;
+    while ii + 1 < b.len() {
+        match (b[ii], b[ii + 1]) {
+            (b'/', b'*') if depth == 0 || nests => {
+                depth += 1;
+                ii += 2;
+            },
+            (b'*', b'/') => {
+                depth -= 1;
+                ii += 2;
+                if depth == 0 {
+                    return ii;
+                }
+            },
+            _ => ii += 1,
+        }
+    }
+    b.len()
+}
+
+// quoted_end finds the end of a literal that closes with the quote it opens
+// with, skipping over backslash escapes.
+fn quoted_end(b: &[u8], start: usize) -> usize {
+    let quote = b[start];
+    let mut ii = start + 1;
+    while ii < b.len() {
+        match b[ii] {
+            b'\\' => ii += 2,
+            c if c == quote => return ii + 1,
+            _ => ii += 1,
+        }
+    }
+    b.len()
+}
+
+// char_end finds the end of a char literal: 'x', '\n' or '\u{1F980}'. A quote
+// that doesn't start one, like Rust's 'a lifetimes, gives None.
+fn char_end(src: &str, start: usize) -> Option<usize> {
+    let b = src.as_bytes();
+    if b.get(start + 1) == Some(&b'\\') {
+        let close = b.get(start + 3..)?.iter().take(10).position(|&c| c == b'\'')?;
+        return Some(start + 3 + close + 1);
+    }
+    let c = src[start + 1..].chars().next()?;
+    let close = start + 1 + c.len_utf8();
+    match b.get(close) {
+        Some(b'\'') if c != '\'' => Some(close + 1),
+        _ => None,
+    }
+}
+
+// raw_end finds the end of a Rust raw string, r"..." or br#"..."#. Anything
+// else starting with r or b gives None.
+fn raw_end(b: &[u8], start: usize) -> Option<usize> {
+    let mut ii = start;
+    if b[ii] == b'b' {
+        ii += 1;
+    }
+    if b.get(ii) != Some(&b'r') {
+        return None;
+    }
+    ii += 1;
+    let hashes = b[ii..].iter().take_while(|&&c| c == b'#').count();
+    ii += hashes;
+    if b.get(ii) != Some(&b'"') {
+        return None;
+    }
+    ii += 1;
+    while ii < b.len() {
+        if b[ii] == b'"' && b[ii + 1..].iter().take(hashes).filter(|&&c| c == b'#').count() == hashes {
+            return Some(ii + 1 + hashes);
+        }
+        ii += 1;
+    }
+    Some(b.len())
+}
+
+// in_ident reports whether the byte at ii carries on an identifier, like the
+// r in bar"...", so it can't start a raw string.
+fn in_ident(b: &[u8], ii: usize) -> bool {
+    ii > 0 && (b[ii - 1].is_ascii_alphanumeric() || b[ii - 1] == b'_')
+}
+
+// search finds the lines of contents where the query appears inside a region
+// of the wanted kind, from start to end.
+pub fn search<'a>(cfg: &Config, contents: &'a str, lang: Lang, want: Kind) -> Vec<Match<'a>> {
+    let kinds = classify(contents, lang);
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    for (ii, raw) in contents.split_inclusive('\n').enumerate() {
+        let line = raw.trim_end_matches('\n').trim_end_matches('\r');
+        let found = find_all(&cfg.query, line, cfg.case_sensitive)
+            .into_iter()
+            .filter(|&(start, end)| end > start && kinds[offset + start..offset + end].iter().all(|&k| k == want))
+            .count();
+        if found > 0 {
+            matches.push(Match { line_number: ii + 1, line, distance: None, found });
+        }
+        offset += raw.len();
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r##"// count the items
+fn count<'a>(items: &'a [&str]) -> usize { /* outer /* nested */ items */
+    let s = "items: \"many\""; let c = '"'; let r = r#"items "# ;
+    items.len() // items
+}
+"##;
+
+    // kinds lists the text of each region of src of the given kind.
+    fn kinds(src: &str, lang: Lang, want: Kind) -> Vec<String> {
+        let kinds = classify(src, lang);
+        let mut regions = Vec::new();
+        let mut current = String::new();
+        for (ii, c) in src.char_indices() {
+            if kinds[ii] == want {
+                current.push(c);
+            } else if !current.is_empty() {
+                regions.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.is_empty() {
+            regions.push(current);
+        }
+        regions
+    }
+
+    #[test]
+    fn classifies_rust() {
+        assert_eq!(
+            vec!["// count the items", "/* outer /* nested */ items */", "// items"],
+            kinds(RUST, Lang::Rust, Kind::Comment)
+        );
+        assert_eq!(
+            vec![r#""items: \"many\"""#, r#"'"'"#, r##"r#"items "#"##],
+            kinds(RUST, Lang::Rust, Kind::Str)
+        );
+    }
+
+    #[test]
+    fn classifies_c() {
+        let src = "/* a /* b */ int x = 'y'; char *s = \"/* no */\"; // end\n";
+        assert_eq!(vec!["/* a /* b */", "// end"], kinds(src, Lang::CLike, Kind::Comment));
+        assert_eq!(vec!["'y'", "\"/* no */\""], kinds(src, Lang::CLike, Kind::Str));
+    }
+
+    #[test]
+    fn searches_regions() {
+        let cfg = Config { query: "items".to_string(), case_sensitive: true, ..Default::default() };
+        let lines = |want: Kind| -> Vec<usize> {
+            search(&cfg, RUST, Lang::Rust, want).iter().map(|m| m.line_number).collect()
+        };
+        assert_eq!(vec![2, 4], lines(Kind::Code));
+        assert_eq!(vec![1, 2, 4], lines(Kind::Comment));
+        assert_eq!(vec![3], lines(Kind::Str));
+    }
+}
@@ -0,0 +1,286 @@
+use config::{Opt, OPTIONS};
+use server;
+
+// Shell is a shell --generate-completions can write a completion script for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(s: &str) -> Result<Shell, String> {
+        match s.trim() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("shell {} should be bash, zsh or fish", s)),
+        }
+    }
+}
+
+// Values says how to complete the value of an option, going by what the
+// option table names it.
+enum Values {
+    Choices(&'static [&'static str]),
+    Dirs,
+    Commands,
+    // Anything else is typed in by hand.
+    Free,
+}
+
+fn values(opt: &Opt) -> Values {
+    match opt.value {
+        Some("WHEN") => Values::Choices(&["auto", "always", "never"]),
+        Some("SHELL") => Values::Choices(&["bash", "zsh", "fish"]),
+        Some("KINDS") => Values::Choices(&["timestamps", "uuids", "hex", "numbers", "all"]),
+        Some("DIR") => Values::Dirs,
+        Some("COMMAND") => Values::Commands,
+        _ => Values::Free,
+    }
+}
+
+// script writes the completion script for shell, from the same option tables
+// the command line is parsed with: minigrep's, and after "serve" the
+// server's. The query isn't completed; the path is completed as a file.
+//
+//     minigrep --generate-completions bash > /etc/bash_completion.d/minigrep
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+// names lists the ways an option can be written: "-U --multiline".
+fn names(opt: &Opt) -> Vec<String> {
+    let mut names: Vec<String> = opt.short.iter().map(|c| format!("-{}", c)).collect();
+    names.push(format!("--{}", opt.long));
+    names
+}
+
+fn bash() -> String {
+    let mut cases = String::new();
+    for opt in OPTIONS.iter().chain(server::OPTIONS).filter(|o| o.value.is_some()) {
+        let reply = match values(opt) {
+            Values::Choices(choices) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", choices.join(" ")),
+            Values::Dirs => "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string(),
+            Values::Commands => "COMPREPLY=($(compgen -c -- \"$cur\"))".to_string(),
+            Values::Free => "COMPREPLY=()".to_string(),
+        };
+        cases += &format!("        {})\n            {}\n            return\n            ;;\n", names(opt).join("|"), reply);
+    }
+    let flags: Vec<String> = OPTIONS.iter().flat_map(names).collect();
+    let serve_flags: Vec<String> = server::OPTIONS.iter().flat_map(names).collect();
+    format!(
+        r#"# bash completion for minigrep
+_minigrep() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+{}    esac
+    if [[ "${{COMP_WORDS[1]}}" == serve && $COMP_CWORD -gt 1 ]]; then
+        COMPREPLY=($(compgen -W "{}" -- "$cur"))
+        return
+    fi
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{}" -- "$cur"))
+        return
+    fi
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "serve" -- "$cur"))
+        return
+    fi
+    COMPREPLY=($(compgen -f -- "$cur"))
+}}
+complete -o filenames -F _minigrep minigrep
+"#,
+        cases,
+        serve_flags.join(" "),
+        flags.join(" ")
+    )
+}
+
+fn zsh() -> String {
+    format!(
+        "#compdef minigrep\n\n\
+         if [[ $words[2] == serve ]] && (( CURRENT > 2 )); then\n  \
+         _arguments \\\n{}  '1:command:(serve)'\n  \
+         return\n\
+         fi\n\n\
+         _arguments -s \\\n{}  '1:query: ' \\\n  '2:path:_files'\n",
+        zsh_specs(server::OPTIONS),
+        zsh_specs(OPTIONS)
+    )
+}
+
+// zsh_specs writes an _arguments spec for each of opts, one per line.
+fn zsh_specs(opts: &[Opt]) -> String {
+    // Descriptions go in [...] inside '...'.
+    let quote = |s: &str| s.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]");
+    let mut specs = String::new();
+    for opt in opts {
+        let repeat = if opt.repeatable { "*" } else { "" };
+        let (short, long) = match opt.value {
+            Some(_) => (opt.short.map(|c| format!("-{}+", c)), format!("--{}=", opt.long)),
+            None => (opt.short.map(|c| format!("-{}", c)), format!("--{}", opt.long)),
+        };
+        let value = match (opt.value, values(opt)) {
+            (None, _) => String::new(),
+            (Some(name), Values::Choices(choices)) => format!(":{}:({})", name, choices.join(" ")),
+            (Some(name), Values::Dirs) => format!(":{}:_files -/", name),
+            (Some(name), Values::Commands) => format!(":{}:_command_names -e", name),
+            (Some(name), Values::Free) => format!(":{}: ", quote(name)),
+        };
+        let spec = match short {
+            Some(short) if repeat.is_empty() => format!(
+                "'(-{} --{})'{{{},{}}}'[{}]{}'",
+                opt.short.unwrap_or_default(), opt.long, short, long, quote(opt.help), value
+            ),
+            Some(short) => format!("{}{{{},{}}}'[{}]{}'", repeat, short, long, quote(opt.help), value),
+            None => format!("'{}{}[{}]{}'", repeat, long, quote(opt.help), value),
+        };
+        specs += &format!("  {} \\\n", spec);
+    }
+    specs
+}
+
+fn fish() -> String {
+    let mut script = String::from("# fish completion for minigrep\n");
+    script += "complete -c minigrep -n '__fish_use_subcommand' -a serve -d 'serve searches over HTTP'\n";
+    script += &fish_lines(OPTIONS, "");
+    script += &fish_lines(server::OPTIONS, " -n '__fish_seen_subcommand_from serve'");
+    script
+}
+
+// fish_lines writes a complete command for each of opts, with condition
+// added to each.
+fn fish_lines(opts: &[Opt], condition: &str) -> String {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('\'', "\\'");
+    let mut lines = String::new();
+    for opt in opts {
+        let mut line = format!("complete -c minigrep{}", condition);
+        if let Some(c) = opt.short {
+            line += &format!(" -s {}", c);
+        }
+        line += &format!(" -l {}", opt.long);
+        match (opt.value, values(opt)) {
+            (None, _) => {},
+            (Some(_), Values::Choices(choices)) => line += &format!(" -x -a '{}'", choices.join(" ")),
+            (Some(_), Values::Dirs) => line += " -x -a '(__fish_complete_directories)'",
+            (Some(_), Values::Commands) => line += " -x -a '(__fish_complete_command)'",
+            (Some(_), Values::Free) => line += " -x",
+        }
+        lines += &format!("{} -d '{}'\n", line, quote(opt.help));
+    }
+    lines
+}
+
+// man_page writes minigrep.1, in roff, from the option tables.
+//
+//     minigrep --generate-man > /usr/local/share/man/man1/minigrep.1
+pub fn man_page() -> String {
+    let mut page = String::from(
+        r#".TH MINIGREP 1
+.SH NAME
+minigrep \- search files for lines containing a query
+.SH SYNOPSIS
+.B minigrep
+[\fIOPTIONS\fR] \fIQUERY\fR \fIPATH\fR
+.br
+.B minigrep serve
+[\fISERVE OPTIONS\fR]
+.SH DESCRIPTION
+.B minigrep
+prints the lines of \fIPATH\fR that contain \fIQUERY\fR, ignoring case unless
+\fB\-\-match\-case\fR is given. \fIPATH\fR can be a file, a directory, which is
+searched recursively, or \fB\-\fR for standard input.
+.PP
+.B minigrep serve
+answers searches of the files under a directory over HTTP, as JSON.
+.SH OPTIONS
+"#,
+    );
+    page += &man_options(OPTIONS);
+    page += ".SH SERVE OPTIONS\n";
+    page += &man_options(server::OPTIONS);
+    page += r#".SH ENVIRONMENT
+.TP
+.B MATCH_CASE
+If set, match case sensitively, as with \fB\-\-match\-case\fR.
+.TP
+.B PAGER
+The pager for results longer than the terminal, if \fB\-\-pager\fR isn't
+given. Defaults to \fBless \-R\fR.
+.SH FILES
+.TP
+.I ~/.config/minigrep/config
+The user's options, one per line, applied before any others.
+.TP
+.I .minigreprc
+The project's options, from the nearest one found walking up from the
+working directory, applied after the user's and before the command line.
+"#;
+    page
+}
+
+fn man_options(opts: &[Opt]) -> String {
+    let mut options = String::new();
+    for opt in opts {
+        let names: Vec<String> = names(opt).iter().map(|n| format!("\\fB{}\\fR", roff(n))).collect();
+        let value = opt.value.map_or(String::new(), |v| format!(" \\fI{}\\fR", roff(v)));
+        options += &format!(".TP\n{}{}\n{}\n", names.join(", "), value, roff(opt.help));
+    }
+    options
+}
+
+// roff escapes text for a man page: backslashes and dashes, and a leading dot
+// or quote that would otherwise start a request.
+fn roff(s: &str) -> String {
+    let escaped = s.replace('\\', "\\e").replace('-', "\\-");
+    match escaped.starts_with('.') || escaped.starts_with('\'') {
+        true => format!("\\&{}", escaped),
+        false => escaped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_option_is_completed() {
+        for shell in &[Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(*shell);
+            assert!(script.contains("serve"), "{:?} is missing serve", shell);
+            for opt in OPTIONS.iter().chain(server::OPTIONS) {
+                assert!(script.contains(opt.long), "{:?} is missing --{}", shell, opt.long);
+            }
+        }
+        let page = man_page();
+        for opt in OPTIONS.iter().chain(server::OPTIONS) {
+            assert!(page.contains(&format!("\\fB\\-\\-{}\\fR", roff(opt.long))), "man page is missing --{}", opt.long);
+        }
+    }
+
+    #[test]
+    fn completes_values() {
+        assert!(bash().contains("        --color)\n            COMPREPLY=($(compgen -W \"auto always never\" -- \"$cur\"))"));
+        assert!(zsh().contains("'(-U --multiline)'{-U,--multiline}'[match the query across line boundaries]'"));
+        assert!(zsh().contains("'*--near=[also require TERM nearby (repeatable)]:TERM: '"));
+        assert!(zsh().contains("'*--mask=[with --aggregate, mask"));
+        assert!(zsh().contains("'*--pre-glob=[only use --pre"));
+        assert!(fish().contains("complete -c minigrep -l relative-to -x -a '(__fish_complete_directories)' -d 'print paths relative to DIR'\n"));
+        assert_eq!(Shell::Zsh, Shell::parse("zsh").unwrap());
+        assert!(Shell::parse("tcsh").is_err());
+    }
+
+    #[test]
+    fn escapes_roff() {
+        assert_eq!("\\-\\-max\\-columns", roff("--max-columns"));
+        assert_eq!("\\&.minigreprc", roff(".minigreprc"));
+        assert_eq!("a\\eb", roff("a\\b"));
+    }
+}
@@ -1,40 +1,925 @@
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use aggregate::Masks;
 use arg::Arg;
+use code::Kind;
+use completions::Shell;
+use filter::{Size, Time};
+use highlight::When;
+use jsonpath::JsonPath;
+use range::Range;
+use window::Format;
 
+// Config is also what the search server reads from request bodies, so the
+// fields that only make sense on the command line are skipped.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub query: String,
     pub path: String,
     pub case_sensitive: bool,
+    pub archives: bool,
+    pub fuzzy: Option<usize>,
+    pub multiline: bool,
+    pub near: Vec<String>,
+    pub within: Option<usize>,
+    #[serde(skip)]
+    pub print_config: bool,
+    #[serde(skip)]
+    pub generate_completions: Option<Shell>,
+    #[serde(skip)]
+    pub generate_man: bool,
+    #[serde(skip)]
+    pub tui: bool,
+    pub vimgrep: bool,
+    pub emacs: bool,
+    pub lines: Option<Range>,
+    pub bytes: Option<Range>,
+    pub line_number: bool,
+    pub newer_than: Option<Time>,
+    pub older_than: Option<Time>,
+    pub max_filesize: Option<Size>,
+    pub min_filesize: Option<Size>,
+    pub since: Option<Time>,
+    pub until: Option<Time>,
+    pub time_format: Option<Format>,
+    // sorted_times says the timestamps are in order, so --since and --until
+    // can binary search for where the window is instead of reading it all.
+    pub sorted_times: bool,
+    pub csv: bool,
+    pub tsv: bool,
+    pub field: Option<String>,
+    pub header: bool,
+    pub json_path: Option<JsonPath>,
+    pub json_value: bool,
+    pub code_only: bool,
+    pub comments_only: bool,
+    pub strings_only: bool,
+    pub stats: bool,
+    #[serde(skip)]
+    pub files_with_matches: bool,
+    #[serde(skip)]
+    pub null: bool,
+    #[serde(skip)]
+    pub path_separator: Option<String>,
+    #[serde(skip)]
+    pub relative_to: Option<String>,
+    #[serde(skip)]
+    pub passthru: bool,
+    #[serde(skip)]
+    pub color: When,
+    #[serde(skip)]
+    pub max_columns: Option<usize>,
+    #[serde(skip)]
+    pub max_columns_preview: Option<usize>,
+    #[serde(skip)]
+    pub aggregate: bool,
+    #[serde(skip)]
+    pub mask: Masks,
+    #[serde(skip)]
+    pub top: Option<usize>,
+    #[serde(skip)]
+    pub pager: Option<String>,
+    #[serde(skip)]
+    pub no_pager: bool,
+    // The server mustn't run commands it's sent.
+    #[serde(skip)]
+    pub pre: Option<String>,
+    #[serde(skip)]
+    pub pre_glob: Vec<String>,
+    // sources lists the config files that were read, in the order they were
+    // applied.
+    #[serde(skip)]
+    pub sources: Vec<String>,
+    // options lists the options that were applied, by long name with their
+    // values, for --print-config. A later one replaces an earlier one unless
+    // the option is repeatable.
+    #[serde(skip)]
+    pub options: Vec<(&'static str, Option<String>)>,
 }
 
+// Opt describes a single command line option.
+pub struct Opt {
+    pub long: &'static str,
+    pub short: Option<char>,
+    // value names the value the option takes, if it takes one.
+    pub value: Option<&'static str>,
+    // repeatable is set when giving the option again adds to it rather than
+    // replacing it.
+    pub repeatable: bool,
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[Opt] = &[
+    Opt { long: "match-case", short: None, value: None, repeatable: false, help: "match the query case sensitively" },
+    Opt { long: "archives", short: None, value: None, repeatable: false, help: "search the members of tar, tar.gz and zip files" },
+    Opt { long: "fuzzy", short: None, value: Some("N"), repeatable: false, help: "match substrings within N edits of the query" },
+    Opt { long: "multiline", short: Some('U'), value: None, repeatable: false, help: "match the query across line boundaries" },
+    Opt { long: "near", short: None, value: Some("TERM"), repeatable: true, help: "also require TERM nearby (repeatable)" },
+    Opt { long: "within", short: None, value: Some("N"), repeatable: false, help: "how many lines apart --near terms may be" },
+    Opt { long: "tui", short: None, value: None, repeatable: false, help: "browse results interactively" },
+    Opt { long: "vimgrep", short: None, value: None, repeatable: false, help: "print every match as path:line:col:text" },
+    Opt { long: "emacs", short: None, value: None, repeatable: false, help: "print matching lines as path:line:col: text" },
+    Opt { long: "lines", short: None, value: Some("START:END"), repeatable: false, help: "only search lines START to END, counting from 1" },
+    Opt { long: "bytes", short: None, value: Some("START:END"), repeatable: false, help: "only search bytes START up to END, counting from 0" },
+    Opt { long: "line-number", short: Some('n'), value: None, repeatable: false, help: "print the line number of each match" },
+    Opt { long: "newer-than", short: None, value: Some("TIME"), repeatable: false, help: "only search files modified after TIME (2h, 7d or 2026-10-01)" },
+    Opt { long: "older-than", short: None, value: Some("TIME"), repeatable: false, help: "only search files modified before TIME" },
+    Opt { long: "max-filesize", short: None, value: Some("SIZE"), repeatable: false, help: "skip files bigger than SIZE (512, 64K, 10M)" },
+    Opt { long: "min-filesize", short: None, value: Some("SIZE"), repeatable: false, help: "skip files smaller than SIZE" },
+    Opt { long: "since", short: None, value: Some("TIME"), repeatable: false, help: "only search lines timestamped at or after TIME (2h or 2026-10-01T13:30)" },
+    Opt { long: "until", short: None, value: Some("TIME"), repeatable: false, help: "only search lines timestamped before TIME" },
+    Opt { long: "sorted-times", short: None, value: None, repeatable: false, help: "binary search for the --since/--until window, trusting the timestamps to be in order" },
+    Opt { long: "time-format", short: None, value: Some("FORMAT"), repeatable: false, help: "read line timestamps as FORMAT (%Y-%m-%d %H:%M:%S)" },
+    Opt { long: "json-path", short: None, value: Some("PATH"), repeatable: false, help: "match values at PATH ($.request.user) in JSON or JSON Lines records" },
+    Opt { long: "json-value", short: None, value: None, repeatable: false, help: "print the matching values instead of whole records" },
+    Opt { long: "code-only", short: None, value: None, repeatable: false, help: "only match code, not comments or string literals" },
+    Opt { long: "comments-only", short: None, value: None, repeatable: false, help: "only match inside comments" },
+    Opt { long: "strings-only", short: None, value: None, repeatable: false, help: "only match inside string and char literals" },
+    Opt { long: "files-with-matches", short: Some('l'), value: None, repeatable: false, help: "only print the names of files with matches" },
+    Opt { long: "null", short: Some('0'), value: None, repeatable: false, help: "end file names with NUL instead of a newline or ':'" },
+    Opt { long: "path-separator", short: None, value: Some("SEP"), repeatable: false, help: "print SEP between path components" },
+    Opt { long: "relative-to", short: None, value: Some("DIR"), repeatable: false, help: "print paths relative to DIR" },
+    Opt { long: "passthru", short: None, value: None, repeatable: false, help: "print every line, marking and highlighting the matches" },
+    Opt { long: "color", short: None, value: Some("WHEN"), repeatable: false, help: "highlight matches: auto (on a terminal), always or never" },
+    Opt { long: "max-columns", short: Some('M'), value: Some("N"), repeatable: false, help: "shorten lines longer than N bytes to a preview around the match" },
+    Opt { long: "max-columns-preview", short: None, value: Some("N"), repeatable: false, help: "make --max-columns previews N bytes long" },
+    Opt { long: "aggregate", short: None, value: None, repeatable: false, help: "count the distinct matching lines and print the most common" },
+    Opt { long: "mask", short: None, value: Some("KINDS"), repeatable: true, help: "with --aggregate, mask timestamps, uuids, hex, numbers or all (comma separated, repeatable)" },
+    Opt { long: "top", short: None, value: Some("N"), repeatable: false, help: "with --aggregate, print the N most common lines (default 10)" },
+    Opt { long: "pager", short: None, value: Some("COMMAND"), repeatable: false, help: "page long output through COMMAND instead of $PAGER or less -R" },
+    Opt { long: "no-pager", short: None, value: None, repeatable: false, help: "don't page output longer than the terminal" },
+    Opt { long: "stats", short: None, value: None, repeatable: false, help: "print a summary of what was searched and how long it took" },
+    Opt { long: "pre", short: None, value: Some("COMMAND"), repeatable: false, help: "search what COMMAND prints for each file instead of the file" },
+    Opt { long: "pre-glob", short: None, value: Some("GLOB"), repeatable: true, help: "only use --pre on files matching GLOB (repeatable)" },
+    Opt { long: "csv", short: None, value: None, repeatable: false, help: "search the fields of comma separated records" },
+    Opt { long: "tsv", short: None, value: None, repeatable: false, help: "search the fields of tab separated records" },
+    Opt { long: "field", short: None, value: Some("NAME|INDEX"), repeatable: false, help: "only search the named or numbered (from 1) column" },
+    Opt { long: "header", short: None, value: None, repeatable: false, help: "print the header record before matching records" },
+    Opt { long: "no-config", short: None, value: None, repeatable: false, help: "don't read any config files" },
+    Opt { long: "print-config", short: None, value: None, repeatable: false, help: "print the merged configuration and exit" },
+    Opt { long: "generate-completions", short: None, value: Some("SHELL"), repeatable: false, help: "print a completion script for bash, zsh or fish and exit" },
+    Opt { long: "generate-man", short: None, value: None, repeatable: false, help: "print the man page and exit" },
+];
+
 impl Config {
-    pub fn from_args<I>(args: I) -> Result<Config, String> 
+    // from_args builds a config from, lowest precedence first: the user's
+    // config file, the project's .minigreprc, environment variables, then args.
+    pub fn from_args<I>(args: I) -> Result<Config, String>
+        where I: IntoIterator<Item = String>,
+    {
+        let mut args: Vec<String> = args.into_iter().collect();
+        // MATCH_CASE goes first, so it's overridden by the args but not by
+        // config files.
+        if env::var("MATCH_CASE").is_ok() && !args.is_empty() {
+            args.insert(1, "--match-case".to_string());
+        }
+        Config::from_sources(args, &config_files())
+    }
+
+    // from_sources builds a config from files, then args, without looking at
+    // the environment.
+    pub fn from_sources<I>(args: I, files: &[PathBuf]) -> Result<Config, String>
         where I: IntoIterator<Item = String>,
     {
         let mut args = args.into_iter();
         args.next();
+        let (flags, positional) = parse(args)?;
+
+        let mut cfg = Config::default();
+        if !flags.iter().any(|f| f.opt.long == "no-config") {
+            for path in files {
+                cfg.apply_file(path)?;
+            }
+        }
+        for f in &flags {
+            cfg.apply(f)?;
+        }
 
-        let query = match args.next() {
-            Some(query) => query,
+        // The query can be left out of an interactive search and typed in
+        // once it starts.
+        let mut positional = positional.into_iter();
+        if cfg.tui && positional.len() == 1 {
+            cfg.path = positional.next().unwrap_or_default();
+        }
+        match positional.next() {
+            Some(query) => cfg.query = query,
+            None if cfg.prints_info() || !cfg.path.is_empty() => {},
             None => return Err("no query provided".to_string()),
-        };
-        let path = match args.next() {
-            Some(path) => path,
+        }
+        match positional.next() {
+            Some(path) => cfg.path = path,
+            None if cfg.prints_info() || !cfg.path.is_empty() => {},
             None => return Err("no path provided".to_string()),
+        }
+        if let Some(arg) = positional.next() {
+            return Err(format!("unexpected argument {}", arg));
+        }
+
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    // apply_file applies the options in a config file. Each line holds one
+    // option written the way it would be on the command line, and the leading
+    // dashes may be left off. A value in double quotes is read as a JSON
+    // string, for values with spaces at the ends or line breaks:
+    //
+    //     # always search case sensitively
+    //     match-case
+    //     --fuzzy 1
+    //     near " timeout"
+    fn apply_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
         };
+        let in_file = |err: String| format!("{}: {}", path.display(), err);
+        let mut args = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = match line.starts_with('-') {
+                true => line.to_string(),
+                false => format!("--{}", line),
+            };
+            // Written with a space, the value is joined on with '=', so it
+            // stays one argument and flags like --line-number can take it.
+            match line.split_once(char::is_whitespace) {
+                Some((flag, value)) if !flag.contains('=') => {
+                    args.push(format!("{}={}", flag, unquote(value.trim_start()).map_err(in_file)?));
+                },
+                _ => args.push(line),
+            }
+        }
+        let (flags, positional) = parse(args).map_err(in_file)?;
+        if let Some(arg) = positional.first() {
+            return Err(in_file(format!("unexpected argument {}", arg)));
+        }
+        for f in &flags {
+            self.apply(f).map_err(in_file)?;
+        }
+        self.sources.push(path.display().to_string());
+        Ok(())
+    }
+
+    // to_file writes out the options that were applied, one to a line in the
+    // form apply_file reads, so what --print-config prints can be saved as a
+    // config file.
+    // near "timeout", fuzzy=1 -> "near \"timeout\"\nfuzzy \"1\"\n"
+    pub fn to_file(&self) -> String {
+        let mut out = String::new();
+        for (long, value) in &self.options {
+            match value {
+                Some(value) => out += &format!("{} {}\n", long, ::serde_json::to_string(value).unwrap_or_default()),
+                None => out += &format!("{}\n", long),
+            }
+        }
+        out
+    }
+
+    fn apply(&mut self, flag: &Flag) -> Result<(), String> {
+        let arg = &flag.arg;
+        self.record(flag);
+        match flag.opt.long {
+            "match-case" => self.case_sensitive = arg.get_bool()?,
+            "archives" => self.archives = arg.get_bool()?,
+            "fuzzy" => self.fuzzy = Some(number(flag)?),
+            "multiline" => self.multiline = arg.get_bool()?,
+            "near" => self.near.push(flag.value.clone().unwrap_or_default()),
+            "within" => self.within = Some(number(flag)?),
+            "print-config" => self.print_config = arg.get_bool()?,
+            "generate-completions" => self.generate_completions = Some(parsed(flag, Shell::parse)?),
+            "generate-man" => self.generate_man = arg.get_bool()?,
+            "tui" => self.tui = arg.get_bool()?,
+            "vimgrep" => self.vimgrep = arg.get_bool()?,
+            "emacs" => self.emacs = arg.get_bool()?,
+            "lines" => self.lines = Some(range(flag)?),
+            "bytes" => self.bytes = Some(range(flag)?),
+            "line-number" => self.line_number = arg.get_bool()?,
+            "newer-than" => self.newer_than = Some(parsed(flag, Time::parse)?),
+            "older-than" => self.older_than = Some(parsed(flag, Time::parse)?),
+            "max-filesize" => self.max_filesize = Some(parsed(flag, Size::parse)?),
+            "min-filesize" => self.min_filesize = Some(parsed(flag, Size::parse)?),
+            "since" => self.since = Some(parsed(flag, Time::parse)?),
+            "until" => self.until = Some(parsed(flag, Time::parse)?),
+            "time-format" => self.time_format = Some(parsed(flag, Format::parse)?),
+            "sorted-times" => self.sorted_times = arg.get_bool()?,
+            "csv" => self.csv = arg.get_bool()?,
+            "tsv" => self.tsv = arg.get_bool()?,
+            "field" => self.field = flag.value.clone(),
+            "header" => self.header = arg.get_bool()?,
+            "json-path" => self.json_path = Some(parsed(flag, JsonPath::parse)?),
+            "json-value" => self.json_value = arg.get_bool()?,
+            "code-only" => self.code_only = arg.get_bool()?,
+            "comments-only" => self.comments_only = arg.get_bool()?,
+            "strings-only" => self.strings_only = arg.get_bool()?,
+            "stats" => self.stats = arg.get_bool()?,
+            "files-with-matches" => self.files_with_matches = arg.get_bool()?,
+            "null" => self.null = arg.get_bool()?,
+            "path-separator" => self.path_separator = flag.value.clone(),
+            "relative-to" => self.relative_to = flag.value.clone(),
+            "passthru" => self.passthru = arg.get_bool()?,
+            "color" => self.color = parsed(flag, When::parse)?,
+            "max-columns" => self.max_columns = Some(number(flag)?),
+            "max-columns-preview" => self.max_columns_preview = Some(number(flag)?),
+            "aggregate" => self.aggregate = arg.get_bool()?,
+            "mask" => self.mask = self.mask.union(parsed(flag, Masks::parse)?),
+            "top" => self.top = Some(number(flag)?),
+            "pager" => self.pager = flag.value.clone(),
+            "no-pager" => self.no_pager = arg.get_bool()?,
+            "pre" => self.pre = flag.value.clone(),
+            "pre-glob" => self.pre_glob.push(flag.value.clone().unwrap_or_default()),
+            _ => {},
+        }
+        Ok(())
+    }
+
+    // record adds flag to the options that were applied. The options that
+    // only say what to do this time aren't kept.
+    fn record(&mut self, flag: &Flag) {
+        let long = flag.opt.long;
+        if long == "print-config" || long == "no-config" {
+            return;
+        }
+        if !flag.opt.repeatable {
+            self.options.retain(|&(l, _)| l != long);
+        }
+        let value = match flag.opt.value {
+            Some(_) => flag.value.clone(),
+            None => flag.arg.split_once('=').map(|(_, v)| v.to_string()),
+        };
+        self.options.push((long, value));
+    }
 
-        let mut case_sensitive = !env::var("MATCH_CASE").is_err();     
-        let flags = args.filter(|arg| arg.contains("-"));
-        for f in flags {
-            if f.contains("match") && f.contains("case") {
-                case_sensitive = f.get_bool()?;
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fuzzy.is_some() && self.multiline {
+            return Err("--fuzzy can't be used with --multiline".to_string());
+        }
+        if self.near.is_empty() != self.within.is_none() {
+            return Err("--near and --within must be used together".to_string());
+        }
+        if !self.near.is_empty() && (self.fuzzy.is_some() || self.multiline) {
+            return Err("--near can't be used with --fuzzy or --multiline".to_string());
+        }
+        if self.tui && (self.multiline || !self.near.is_empty()) {
+            return Err("--tui can't be used with --multiline or --near".to_string());
+        }
+        if self.vimgrep && self.emacs {
+            return Err("--vimgrep can't be used with --emacs".to_string());
+        }
+        if (self.vimgrep || self.emacs) && (self.multiline || !self.near.is_empty()) {
+            return Err("--vimgrep and --emacs can't be used with --multiline or --near".to_string());
+        }
+        if self.lines.is_some() && self.bytes.is_some() {
+            return Err("--lines can't be used with --bytes".to_string());
+        }
+        if self.csv && self.tsv {
+            return Err("--csv can't be used with --tsv".to_string());
+        }
+        let table = self.csv || self.tsv;
+        if !table && (self.field.is_some() || self.header) {
+            return Err("--field and --header need --csv or --tsv".to_string());
+        }
+        if table && (self.fuzzy.is_some() || self.multiline || !self.near.is_empty()) {
+            return Err("--csv and --tsv can't be used with --fuzzy, --multiline or --near".to_string());
+        }
+        if table && (self.lines.is_some() || self.bytes.is_some()) {
+            return Err("--csv and --tsv need the header, so can't be used with --lines or --bytes".to_string());
+        }
+        // A quoted field can span lines, so a record has no single line and
+        // column for an editor to jump to.
+        if table && (self.vimgrep || self.emacs || self.tui) {
+            return Err("--csv and --tsv can't be used with --vimgrep, --emacs or --tui".to_string());
+        }
+        if self.json_value && self.json_path.is_none() {
+            return Err("--json-value needs --json-path".to_string());
+        }
+        if self.json_path.is_some() && (table || self.fuzzy.is_some() || self.multiline || !self.near.is_empty()) {
+            return Err("--json-path can't be used with --csv, --tsv, --fuzzy, --multiline or --near".to_string());
+        }
+        let regions = [self.code_only, self.comments_only, self.strings_only];
+        if regions.iter().filter(|&&on| on).count() > 1 {
+            return Err("only one of --code-only, --comments-only and --strings-only can be used".to_string());
+        }
+        let special = table || self.json_path.is_some() || self.fuzzy.is_some() || self.multiline || !self.near.is_empty();
+        if self.region().is_some() && special {
+            return Err("--code-only, --comments-only and --strings-only can't be used with other search modes".to_string());
+        }
+        let located = self.vimgrep || self.emacs || self.tui;
+        if self.passthru && (special || located || self.region().is_some()) {
+            return Err("--passthru only works with plain and --fuzzy searches".to_string());
+        }
+        if self.files_with_matches && (self.multiline || !self.near.is_empty() || self.passthru || located) {
+            return Err("-l can't be used with --multiline, --near, --passthru, --vimgrep, --emacs or --tui".to_string());
+        }
+        if !self.aggregate && (self.mask != Masks::default() || self.top.is_some()) {
+            return Err("--mask and --top need --aggregate".to_string());
+        }
+        if self.aggregate && (self.multiline || !self.near.is_empty() || self.passthru || located || self.files_with_matches) {
+            return Err("--aggregate can't be used with --multiline, --near, --passthru, --vimgrep, --emacs, --tui or -l".to_string());
+        }
+        let window = self.since.is_some() || self.until.is_some();
+        if (self.time_format.is_some() || self.sorted_times) && !window {
+            return Err("--time-format and --sorted-times need --since or --until".to_string());
+        }
+        if window && (table || self.json_path.is_some() || self.multiline || !self.near.is_empty() || self.passthru) {
+            return Err("--since and --until can't be used with --csv, --tsv, --json-path, --multiline, --near or --passthru".to_string());
+        }
+        if window && (self.lines.is_some() || self.bytes.is_some()) {
+            return Err("--since and --until can't be used with --lines or --bytes".to_string());
+        }
+        if self.max_columns == Some(0) || self.max_columns_preview == Some(0) {
+            return Err("--max-columns and --max-columns-preview must be at least 1".to_string());
+        }
+        if self.max_columns_preview.is_some() && self.max_columns.is_none() {
+            return Err("--max-columns-preview needs --max-columns".to_string());
+        }
+        if self.path_separator.as_ref().is_some_and(|sep| sep.is_empty()) {
+            return Err("--path-separator can't be empty".to_string());
+        }
+        if !self.pre_glob.is_empty() && self.pre.is_none() {
+            return Err("--pre-glob needs --pre".to_string());
+        }
+        if let (Some(min), Some(max)) = (self.min_filesize, self.max_filesize) {
+            if min.0 > max.0 {
+                return Err("--min-filesize is bigger than --max-filesize".to_string());
             }
         }
-        
-        Ok(Config{
-            query,
-            path,
-            case_sensitive,
-        })
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since.0 >= until.0 {
+                return Err("--since and --until leave no time between them".to_string());
+            }
+        }
+        if let (Some(newer), Some(older)) = (self.newer_than, self.older_than) {
+            if newer.0 >= older.0 {
+                return Err("--newer-than and --older-than leave no time between them".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // prints_info reports whether minigrep will print something about itself
+    // and exit instead of searching, so it doesn't need a query or path.
+    pub fn prints_info(&self) -> bool {
+        self.print_config || self.generate_completions.is_some() || self.generate_man
+    }
+
+    // region is the kind of source code region matches are restricted to, if
+    // any.
+    pub fn region(&self) -> Option<Kind> {
+        if self.code_only {
+            return Some(Kind::Code);
+        }
+        if self.comments_only {
+            return Some(Kind::Comment);
+        }
+        if self.strings_only {
+            return Some(Kind::Str);
+        }
+        None
+    }
+
+    // needs_line_numbers reports whether anything will show where matches
+    // are, which decides whether --bytes has to count the lines it skips.
+    // JSON records are always printed numbered.
+    pub fn needs_line_numbers(&self) -> bool {
+        self.line_number || self.vimgrep || self.emacs || self.within.is_some() || self.tui || self.json_path.is_some()
+    }
+}
+
+// Flag is an option found in the arguments, along with its value if it takes
+// one.
+struct Flag {
+    opt: &'static Opt,
+    // arg is the option as it was written, e.g. "--fuzzy=2".
+    arg: String,
+    value: Option<String>,
+}
+
+// parse splits args into options and positional arguments.
+// Anything after "--" is positional, so queries can start with a dash.
+fn parse<I>(args: I) -> Result<(Vec<Flag>, Vec<String>), String>
+    where I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut flags = Vec::new();
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.extend(args);
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg);
+            continue;
+        }
+        let opt = match lookup(&arg) {
+            Some(opt) => opt,
+            None => return Err(format!("unknown argument {}", arg)),
+        };
+        let value = match opt.value {
+            Some(_) => Some(value(&arg, &mut args)?),
+            None => None,
+        };
+        flags.push(Flag { opt, arg, value });
+    }
+    Ok((flags, positional))
+}
+
+// lookup finds the option an argument refers to.
+// --fuzzy=2 -> Some(fuzzy)
+// -U -> Some(multiline)
+fn lookup(arg: &str) -> Option<&'static Opt> {
+    let name = arg.split('=').next().unwrap_or("");
+    if let Some(long) = name.strip_prefix("--") {
+        return OPTIONS.iter().find(|o| o.long == long);
     }
-}
\ No newline at end of file
+    let mut chars = name[1..].chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => OPTIONS.iter().find(|o| o.short == Some(c)),
+        _ => None,
+    }
+}
+
+// value gets the value of a flag that takes one, either from the flag itself
+// or from the argument that follows it.
+// --some-flag=hello -> Result<"hello">
+// --some-flag hello -> Result<"hello">
+fn value<I>(flag: &str, args: &mut I) -> Result<String, String>
+    where I: Iterator<Item = String>,
+{
+    if flag.contains("=") {
+        return flag.to_string().get_string();
+    }
+    match args.next() {
+        Some(v) => Ok(v),
+        None => Err(format!("argument {} needs a value", flag)),
+    }
+}
+
+// unquote reads a config file value, which is taken as it is unless it's
+// in double quotes.
+// "1" -> Ok("1")
+// "\" a\\nb\"" -> Ok(" a\nb")
+fn unquote(value: &str) -> Result<String, String> {
+    if !value.starts_with('"') {
+        return Ok(value.to_string());
+    }
+    ::serde_json::from_str(value).map_err(|err| format!("quoted value {}: {}", value, err))
+}
+
+// number gets the value of a flag that takes a count.
+fn number(flag: &Flag) -> Result<usize, String> {
+    match flag.value.as_ref().map(|v| v.parse()) {
+        Some(Ok(n)) => Ok(n),
+        _ => Err(format!("argument {} should be a number", flag.arg)),
+    }
+}
+
+// range gets the value of a flag that takes a START:END range.
+fn range(flag: &Flag) -> Result<Range, String> {
+    parsed(flag, Range::parse)
+}
+
+// parsed gets the value of a flag using parse, naming the flag in any error.
+// --max-filesize=lots -> Err("argument --max-filesize=lots: size lots should be ...")
+fn parsed<T, F>(flag: &Flag, parse: F) -> Result<T, String>
+    where F: Fn(&str) -> Result<T, String>,
+{
+    parse(flag.value.as_ref().map_or("", |v| v.as_str()))
+        .map_err(|err| format!("argument {}: {}", flag.arg, err))
+}
+
+// config_files lists the config files that exist, lowest precedence first:
+// ~/.config/minigrep/config, then the nearest .minigreprc found walking up
+// from the working directory.
+fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    };
+    if let Some(dir) = config_dir {
+        let f = dir.join("minigrep").join("config");
+        if f.is_file() {
+            files.push(f);
+        }
+    }
+    if let Ok(mut dir) = env::current_dir() {
+        loop {
+            let f = dir.join(".minigreprc");
+            if f.is_file() {
+                files.push(f);
+                break;
+            }
+            if !dir.pop() {
+                break;
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("minigrep-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn flags_anywhere() {
+        let cfg = Config::from_sources(args(&["exe", "-U", "one", "two"]), &[]).unwrap();
+        assert!(cfg.multiline);
+        let cfg = Config::from_sources(args(&["exe", "--near", "x", "one", "two", "--within=3"]), &[]).unwrap();
+        assert_eq!("one", cfg.query);
+        assert_eq!("two", cfg.path);
+        assert_eq!(vec!["x".to_string()], cfg.near);
+        assert_eq!(Some(3), cfg.within);
+    }
+
+    #[test]
+    fn dash_dash_ends_flags() {
+        let cfg = Config::from_sources(args(&["exe", "--", "-U", "two"]), &[]).unwrap();
+        assert_eq!("-U", cfg.query);
+        assert!(!cfg.multiline);
+    }
+
+    #[test]
+    fn unknown_flag() {
+        assert!(Config::from_sources(args(&["exe", "one", "two", "--nope"]), &[]).is_err());
+    }
+
+    #[test]
+    fn later_sources_win() {
+        let global = write_file("global", "# defaults\nfuzzy 1\n\n--archives\n");
+        let project = write_file("project", "fuzzy=2\n");
+        let files = [global.clone(), project.clone()];
+
+        let cfg = Config::from_sources(args(&["exe", "one", "two"]), &files).unwrap();
+        assert_eq!(Some(2), cfg.fuzzy);
+        assert!(cfg.archives);
+        assert_eq!(2, cfg.sources.len());
+
+        let cfg = Config::from_sources(args(&["exe", "one", "two", "--fuzzy=3", "--archives=false"]), &files).unwrap();
+        assert_eq!(Some(3), cfg.fuzzy);
+        assert!(!cfg.archives);
+
+        let cfg = Config::from_sources(args(&["exe", "one", "two", "--no-config"]), &files).unwrap();
+        assert_eq!(None, cfg.fuzzy);
+        assert!(cfg.sources.is_empty());
+
+        fs::remove_file(global).unwrap();
+        fs::remove_file(project).unwrap();
+    }
+
+    #[test]
+    fn bad_config_file() {
+        let path = write_file("bad", "--fuzzy lots\n");
+        let files = [path.clone()];
+        let err = Config::from_sources(args(&["exe", "one", "two"]), &files).unwrap_err();
+        assert!(err.starts_with(&path.display().to_string()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn one_location_format() {
+        assert!(Config::from_sources(args(&["exe", "a", "b", "--vimgrep"]), &[]).unwrap().vimgrep);
+        assert!(Config::from_sources(args(&["exe", "a", "b", "--vimgrep", "--emacs"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "a", "b", "--emacs", "-U"]), &[]).is_err());
+    }
+
+    #[test]
+    fn tui_needs_no_query() {
+        let cfg = Config::from_sources(args(&["exe", "--tui", "poem.txt"]), &[]).unwrap();
+        assert_eq!("", cfg.query);
+        assert_eq!("poem.txt", cfg.path);
+    }
+
+    #[test]
+    fn file_filters() {
+        let cfg = Config::from_sources(args(&["exe", "a", "logs", "--max-filesize", "10M", "--newer-than=2h"]), &[]).unwrap();
+        assert_eq!(Some(Size(10 << 20)), cfg.max_filesize);
+        assert!(cfg.newer_than.is_some());
+        let err = Config::from_sources(args(&["exe", "a", "logs", "--max-filesize", "lots"]), &[]).unwrap_err();
+        assert!(err.starts_with("argument --max-filesize: size lots"), "{}", err);
+        assert!(Config::from_sources(args(&["exe", "a", "logs", "--min-filesize=1M", "--max-filesize=1K"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "a", "logs", "--newer-than=1h", "--older-than=2h"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "a", "logs", "--newer-than=2h", "--older-than=1h"]), &[]).is_ok());
+    }
+
+    #[test]
+    fn table_flags() {
+        let cfg = Config::from_sources(args(&["exe", "a", "b.csv", "--csv", "--field", "user", "--header"]), &[]).unwrap();
+        assert_eq!(Some("user".to_string()), cfg.field);
+        assert!(Config::from_sources(args(&["exe", "a", "b.csv", "--field", "user"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "a", "b.csv", "--csv", "--tsv"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "a", "b.csv", "--csv", "--lines=2:"]), &[]).is_err());
+        for flag in &["--vimgrep", "--emacs", "--tui"] {
+            assert!(Config::from_sources(args(&["exe", "a", "b.csv", "--tsv", flag]), &[]).is_err(), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn json_flags() {
+        let cfg = Config::from_sources(args(&["exe", "--json-path", "$.request.user", "alice", "events.jsonl"]), &[]).unwrap();
+        assert!(cfg.json_path.is_some());
+        let err = Config::from_sources(args(&["exe", "--json-path=request", "a", "b"]), &[]).unwrap_err();
+        assert!(err.contains("should start with $"), "{}", err);
+        assert!(Config::from_sources(args(&["exe", "--json-value", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn one_region() {
+        let cfg = Config::from_sources(args(&["exe", "--code-only", "a", "b"]), &[]).unwrap();
+        assert_eq!(Some(Kind::Code), cfg.region());
+        assert!(Config::from_sources(args(&["exe", "--code-only", "--strings-only", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--comments-only", "--fuzzy=1", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn passthru_flags() {
+        let cfg = Config::from_sources(args(&["exe", "--passthru", "--color=always", "error", "-"]), &[]).unwrap();
+        assert!(cfg.passthru);
+        assert_eq!(When::Always, cfg.color);
+        assert_eq!("-", cfg.path);
+        assert!(Config::from_sources(args(&["exe", "--color=sometimes", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--passthru", "-U", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn file_name_flags() {
+        let cfg = Config::from_sources(args(&["exe", "-l", "-0", "--relative-to", "logs", "a", "logs"]), &[]).unwrap();
+        assert!(cfg.files_with_matches && cfg.null);
+        assert_eq!(Some("logs".to_string()), cfg.relative_to);
+        assert!(Config::from_sources(args(&["exe", "-l", "--vimgrep", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--path-separator=", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn aggregate_flags() {
+        let cfg = Config::from_sources(args(&["exe", "--aggregate", "--mask=uuids,numbers", "--top", "3", "a", "b"]), &[]).unwrap();
+        assert!(cfg.aggregate);
+        assert_eq!(Masks { uuids: true, numbers: true, ..Default::default() }, cfg.mask);
+        assert_eq!(Some(3), cfg.top);
+        let cfg = Config::from_sources(args(&["exe", "--aggregate", "--mask=uuids", "--mask", "hex", "a", "b"]), &[]).unwrap();
+        assert_eq!(Masks { uuids: true, hex: true, ..Default::default() }, cfg.mask);
+        assert!(Config::from_sources(args(&["exe", "--mask=all", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--aggregate", "--mask=ips", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--aggregate", "--passthru", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn time_window_flags() {
+        let cfg = Config::from_sources(args(&["exe", "--since=2026-10-01", "--until", "2026-10-02", "--time-format=%s", "a", "b"]), &[]).unwrap();
+        assert!(cfg.since.is_some() && cfg.until.is_some());
+        assert_eq!(Some(Format::parse("%s").unwrap()), cfg.time_format);
+        assert!(Config::from_sources(args(&["exe", "--since=2026-10-02", "--until=2026-10-01", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--time-format=%s", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--since=1h", "--sorted-times", "a", "b"]), &[]).unwrap().sorted_times);
+        assert!(Config::from_sources(args(&["exe", "--sorted-times", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--since=1h", "--lines=1:2", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--since=1h", "--time-format=%q", "a", "b"]), &[]).is_err());
+    }
+
+    #[test]
+    fn max_columns_flags() {
+        let cfg = Config::from_sources(args(&["exe", "-M", "200", "--max-columns-preview=80", "a", "b"]), &[]).unwrap();
+        assert_eq!((Some(200), Some(80)), (cfg.max_columns, cfg.max_columns_preview));
+        assert!(Config::from_sources(args(&["exe", "--max-columns-preview=80", "a", "b"]), &[]).is_err());
+        assert!(Config::from_sources(args(&["exe", "--max-columns=0", "a", "b"]), &[]).is_err());
+    }
+
+    // push_flag writes a flag with a value the way it might be typed:
+    // --flag=value, or --flag value when separate is set.
+    fn push_flag(argv: &mut Vec<String>, flag: &str, value: &str, separate: bool) {
+        match separate {
+            true => argv.extend(vec![flag.to_string(), value.to_string()]),
+            false => argv.push(format!("{}={}", flag, value)),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn options_round_trip(
+            query in "\\PC*",
+            path in "[a-z./]{1,12}",
+            fuzzy in proptest::option::of(0usize..4),
+            near in proptest::collection::vec("\\PC+", 0..3),
+            within in 0usize..10,
+            line_number: bool,
+            match_case: bool,
+            separate: bool,
+        ) {
+            prop_assume!(fuzzy.is_none() || near.is_empty());
+            let mut argv = vec!["exe".to_string()];
+            if let Some(n) = fuzzy {
+                push_flag(&mut argv, "--fuzzy", &n.to_string(), separate);
+            }
+            for term in &near {
+                push_flag(&mut argv, "--near", term, separate);
+            }
+            if !near.is_empty() {
+                push_flag(&mut argv, "--within", &within.to_string(), separate);
+            }
+            match separate {
+                true if line_number => argv.push("-n".to_string()),
+                true => {},
+                false => argv.push(format!("--line-number={}", line_number)),
+            }
+            if match_case {
+                argv.push("--match-case".to_string());
+            }
+            argv.extend(vec!["--".to_string(), query.clone(), path.clone()]);
+
+            let cfg = Config::from_sources(argv.clone(), &[]);
+            prop_assert!(cfg.is_ok(), "{:?}: {:?}", argv, cfg.err());
+            let cfg = cfg.unwrap();
+            prop_assert_eq!(query, cfg.query);
+            prop_assert_eq!(path, cfg.path);
+            prop_assert_eq!(fuzzy, cfg.fuzzy);
+            prop_assert_eq!(within, cfg.within.unwrap_or(within));
+            prop_assert_eq!(near.is_empty(), cfg.within.is_none());
+            prop_assert_eq!(near, cfg.near);
+            prop_assert_eq!(line_number, cfg.line_number);
+            prop_assert_eq!(match_case, cfg.case_sensitive);
+        }
+    }
+
+    #[test]
+    fn print_config_needs_no_query() {
+        let cfg = Config::from_sources(args(&["exe", "--print-config"]), &[]).unwrap();
+        assert!(cfg.print_config);
+        let cfg = Config::from_sources(args(&["exe", "--generate-completions", "fish"]), &[]).unwrap();
+        assert_eq!(Some(Shell::Fish), cfg.generate_completions);
+        assert!(Config::from_sources(args(&["exe", "--generate-man"]), &[]).unwrap().generate_man);
+        assert!(Config::from_sources(args(&["exe", "--generate-completions=csh"]), &[]).is_err());
+    }
+
+    #[test]
+    fn printed_config_reads_back() {
+        let argv = args(&[
+            "exe", "-n", "--archives=false", "--fuzzy", "1", "--fuzzy=2", "--pager", "less '+/a b'",
+            "--aggregate", "--mask=uuids", "--mask", "hex", "--path-separator= ", "--print-config",
+        ]);
+        let cfg = Config::from_sources(argv, &[]).unwrap();
+        let file = cfg.to_file();
+        assert_eq!(
+            "line-number\narchives \"false\"\nfuzzy \"2\"\npager \"less '+/a b'\"\naggregate\nmask \"uuids\"\nmask \"hex\"\npath-separator \" \"\n",
+            file
+        );
+        let rc = write_file("printed", &file);
+        let files = [rc.clone()];
+        let again = Config::from_sources(args(&["exe", "--print-config"]), &files).unwrap();
+        assert_eq!(format!("{:?}", cfg), format!("{:?}", Config { sources: Vec::new(), ..again }));
+        fs::remove_file(rc).unwrap();
+
+        let rc = write_file("quoted", "near \" a\\nb\"\nwithin 1\nnear \"x\n");
+        let files = [rc.clone()];
+        let err = Config::from_sources(args(&["exe", "q", "p"]), &files).unwrap_err();
+        assert!(err.contains("quoted value \"x"), "{}", err);
+        fs::write(&rc, "near \" a\\nb\"\nwithin 1\n").unwrap();
+        assert_eq!(vec![" a\nb".to_string()], Config::from_sources(args(&["exe", "q", "p"]), &files).unwrap().near);
+        fs::remove_file(rc).unwrap();
+    }
+
+    // The search server reads these from request bodies, through the same
+    // parsers as the flags.
+    #[test]
+    fn deserializes_typed_fields() {
+        let body = r#"{"newer_than": "2h", "max_filesize": "1k", "lines": "3:", "json_path": "$.msg", "time_format": "%H:%M:%S"}"#;
+        let cfg: Config = ::serde_json::from_str(body).unwrap();
+        assert!(cfg.newer_than.is_some());
+        assert_eq!(Some(Size(1024)), cfg.max_filesize);
+        assert_eq!(Some(Range::parse("3:").unwrap()), cfg.lines);
+        assert_eq!(Some(JsonPath::parse("$.msg").unwrap()), cfg.json_path);
+        assert_eq!(Some(Format::parse("%H:%M:%S").unwrap()), cfg.time_format);
+        for bad in &[r#"{"since": "soon"}"#, r#"{"min_filesize": "lots"}"#, r#"{"bytes": "x"}"#, r#"{"json_path": "msg"}"#] {
+            assert!(::serde_json::from_str::<Config>(bad).is_err(), "{}", bad);
+        }
+        assert!(::serde_json::from_str::<Config>(r#"{"color": "always"}"#).is_err());
+    }
+
+    #[test]
+    fn pager_options() {
+        let rc = write_file("pager", "no-pager\npager less -RS\n");
+        let files = [rc.clone()];
+        let cfg = Config::from_sources(args(&["exe", "one", "two"]), &files).unwrap();
+        assert!(cfg.no_pager);
+        assert_eq!(Some("less -RS".to_string()), cfg.pager);
+        let cfg = Config::from_sources(args(&["exe", "one", "two", "--no-pager=false"]), &files).unwrap();
+        assert!(!cfg.no_pager);
+        fs::remove_file(rc).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use std::mem;
+use {find_all, Config, Match};
+
+// Record is one row of a CSV or TSV file. Quoted fields can hold delimiters,
+// doubled quotes ("") and line breaks, so a record may span several lines.
+#[derive(Debug, PartialEq)]
+pub struct Record<'a> {
+    // line_number is the line the record starts on, counting from 1.
+    pub line_number: usize,
+    // text is the record as written in the file, without its line break.
+    pub text: &'a str,
+    pub fields: Vec<String>,
+}
+
+// Records reads the records of contents one at a time, skipping blank lines.
+pub struct Records<'a> {
+    contents: &'a str,
+    delimiter: u8,
+    pos: usize,
+    line: usize,
+}
+
+pub fn records(contents: &str, delimiter: u8) -> Records<'_> {
+    Records { contents, delimiter, pos: 0, line: 1 }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Record<'a>> {
+        let bytes = self.contents.as_bytes();
+        while self.pos < bytes.len() {
+            let start = self.pos;
+            let line_number = self.line;
+            let mut fields = Vec::new();
+            let mut field = Vec::new();
+            let mut quoted = false;
+            let mut end = bytes.len();
+            while let Some(&b) = bytes.get(self.pos) {
+                self.pos += 1;
+                if quoted {
+                    match b {
+                        b'"' if bytes.get(self.pos) == Some(&b'"') => {
+                            field.push(b'"');
+                            self.pos += 1;
+                        },
+                        b'"' => quoted = false,
+                        b'\n' => {
+                            self.line += 1;
+                            field.push(b);
+                        },
+                        _ => field.push(b),
+                    }
+                    continue;
+                }
+                match b {
+                    b'"' => quoted = true,
+                    b'\n' => {
+                        end = self.pos - 1;
+                        self.line += 1;
+                        break;
+                    },
+                    b'\r' if bytes.get(self.pos) == Some(&b'\n') => {},
+                    _ if b == self.delimiter => fields.push(text(mem::take(&mut field))),
+                    _ => field.push(b),
+                }
+            }
+            fields.push(text(field));
+            let text = self.contents[start..end].trim_end_matches('\r');
+            if text.is_empty() {
+                continue;
+            }
+            return Some(Record { line_number, text, fields });
+        }
+        None
+    }
+}
+
+// text turns the bytes of a field back into a string. Fields are only ever
+// split at ASCII bytes, so they're still valid UTF-8.
+fn text(field: Vec<u8>) -> String {
+    String::from_utf8(field).unwrap_or_default()
+}
+
+// delimiter is the byte that separates fields in the configured format.
+fn delimiter(cfg: &Config) -> u8 {
+    match cfg.tsv {
+        true => b'\t',
+        false => b',',
+    }
+}
+
+// header is the first record of contents.
+pub fn header<'a>(cfg: &Config, contents: &'a str) -> Option<Record<'a>> {
+    records(contents, delimiter(cfg)).next()
+}
+
+// column finds which field --field refers to: a column number counting from
+// 1, or the name of a column in the header.
+// --field 2 -> Ok(Some(1))
+// --field user with header "id,user" -> Ok(Some(1))
+pub fn column(cfg: &Config, contents: &str) -> Result<Option<usize>, String> {
+    let field = match cfg.field {
+        Some(ref field) => field.as_str(),
+        None => return Ok(None),
+    };
+    if let Ok(n) = field.parse::<usize>() {
+        if n == 0 {
+            return Err("--field numbers count from 1".to_string());
+        }
+        return Ok(Some(n - 1));
+    }
+    let names = header(cfg, contents).map(|h| h.fields).unwrap_or_default();
+    match names.iter().position(|name| name.trim() == field) {
+        Some(ii) => Ok(Some(ii)),
+        None => Err(format!("no column {} in header: {}", field, names.join(", "))),
+    }
+}
+
+// search finds the records after the header with a field containing the
+// query, looking only at the --field column if one is given.
+pub fn search<'a>(cfg: &Config, contents: &'a str) -> Vec<Match<'a>> {
+    let column = match column(cfg, contents) {
+        Ok(column) => column,
+        Err(_) => return Vec::new(),
+    };
+    let count = |field: &String| find_all(&cfg.query, field, cfg.case_sensitive).len();
+    records(contents, delimiter(cfg))
+        .skip(1)
+        .filter_map(|r| {
+            let found = match column {
+                Some(ii) => r.fields.get(ii).map_or(0, count),
+                None => r.fields.iter().map(count).sum(),
+            };
+            match found {
+                0 => None,
+                _ => Some(Match { line_number: r.line_number, line: r.text, distance: None, found }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEOPLE: &str = "id,name,note\n\
+1,alice,\"likes rust, and go\"\n\
+2,bob,\"said \"\"hi\"\"\nthen left\"\r\n\
+\n\
+3,rusty,none\n";
+
+    fn fields(contents: &str, delimiter: u8) -> Vec<Vec<String>> {
+        records(contents, delimiter).map(|r| r.fields).collect()
+    }
+
+    #[test]
+    fn quoted_fields() {
+        let all: Vec<Record> = records(PEOPLE, b',').collect();
+        assert_eq!(4, all.len());
+        assert_eq!(vec!["1", "alice", "likes rust, and go"], all[1].fields);
+        assert_eq!(vec!["2", "bob", "said \"hi\"\nthen left"], all[2].fields);
+        assert_eq!("2,bob,\"said \"\"hi\"\"\nthen left\"", all[2].text);
+        assert_eq!(3, all[2].line_number);
+        assert_eq!(6, all[3].line_number);
+        assert_eq!(vec![vec!["a", "b,c"]], fields("a\tb,c", b'\t'));
+        assert_eq!(vec![vec!["", ""]], fields(",", b','));
+    }
+
+    #[test]
+    fn search_by_field() {
+        let mut cfg = Config { query: "rust".to_string(), csv: true, ..Default::default() };
+        let lines = |cfg: &Config| -> Vec<usize> {
+            search(cfg, PEOPLE).iter().map(|m| m.line_number).collect()
+        };
+        assert_eq!(vec![2, 6], lines(&cfg));
+        cfg.field = Some("name".to_string());
+        assert_eq!(vec![6], lines(&cfg));
+        cfg.field = Some("3".to_string());
+        assert_eq!(vec![2], lines(&cfg));
+        cfg.query = "left".to_string();
+        assert_eq!(vec![3], lines(&cfg));
+
+        cfg.field = Some("email".to_string());
+        assert_eq!(Err("no column email in header: id, name, note".to_string()), column(&cfg, PEOPLE));
+        cfg.field = Some("0".to_string());
+        assert!(column(&cfg, PEOPLE).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::convert::TryFrom;
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use Config;
+
+// Time is a point in time given either as an age relative to now ("2h",
+// "1d12h") or as a UTC date ("2026-10-01", "2026-10-01T13:30").
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Time(pub SystemTime);
+
+impl Time {
+    pub fn parse(s: &str) -> Result<Time, String> {
+        Time::parse_at(s, SystemTime::now())
+    }
+
+    fn parse_at(s: &str, now: SystemTime) -> Result<Time, String> {
+        let s = s.trim();
+        if s.len() >= 10 && s.as_bytes()[4] == b'-' {
+            return parse_date(s).map(Time);
+        }
+        match parse_duration(s) {
+            Some(age) => Ok(Time(now.checked_sub(age).unwrap_or(UNIX_EPOCH))),
+            None => Err(format!(
+                "time {} should be an age like 30m, 2h or 7d, or a date like 2026-10-01",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Time {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Time, String> {
+        Time::parse(&s)
+    }
+}
+
+// Size is a number of bytes, written with an optional K, M, G or T suffix
+// (powers of 1024): "512", "64K", "10M", "1.5G".
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Size(pub u64);
+
+impl Size {
+    pub fn parse(s: &str) -> Result<Size, String> {
+        let upper = s.trim().to_uppercase();
+        let digits = upper.trim_end_matches('B').trim_end_matches('I');
+        let (number, scale) = match digits.chars().last() {
+            Some('K') => (&digits[..digits.len() - 1], 1u64 << 10),
+            Some('M') => (&digits[..digits.len() - 1], 1 << 20),
+            Some('G') => (&digits[..digits.len() - 1], 1 << 30),
+            Some('T') => (&digits[..digits.len() - 1], 1 << 40),
+            _ => (digits, 1),
+        };
+        match number.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 => Ok(Size((n * scale as f64) as u64)),
+            _ => Err(format!("size {} should be a number of bytes like 512, 64K, 10M or 1G", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Size {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Size, String> {
+        Size::parse(&s)
+    }
+}
+
+// allows reports whether a file passes the size and modification time
+// filters in the config. Files whose modification time can't be read are
+// let through.
+pub fn allows(cfg: &Config, meta: &Metadata) -> bool {
+    allows_entry(cfg, meta.len(), meta.modified().ok())
+}
+
+// allows_entry is allows for something that isn't a file on disk, like an
+// archive member, given its size and modification time.
+pub fn allows_entry(cfg: &Config, size: u64, modified: Option<SystemTime>) -> bool {
+    if cfg.max_filesize.is_some_and(|max| size > max.0) {
+        return false;
+    }
+    if cfg.min_filesize.is_some_and(|min| size < min.0) {
+        return false;
+    }
+    if let Some(modified) = modified {
+        if cfg.newer_than.is_some_and(|t| modified <= t.0) {
+            return false;
+        }
+        if cfg.older_than.is_some_and(|t| modified >= t.0) {
+            return false;
+        }
+    }
+    true
+}
+
+// parse_duration reads an age made of one or more number and unit pairs,
+// where the units are s, m, h, d and w.
+// "90m" -> 90 minutes
+// "1d12h" -> 36 hours
+fn parse_duration(s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut secs = 0u64;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let n: u64 = number.parse().ok()?;
+        secs = secs.checked_add(n.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+// parse_date reads a UTC date, with an optional time of day.
+// "2026-10-01" -> midnight on the 1st of October 2026
+// "2026-10-01T13:30" or "2026-10-01 13:30:15" -> that time on that day
+fn parse_date(s: &str) -> Result<SystemTime, String> {
+    let bad = || format!("date {} should look like 2026-10-01 or 2026-10-01T13:30:00", s);
+    let (date, time) = match s.find(['T', ' ']) {
+        Some(ii) => (&s[..ii], &s[ii + 1..]),
+        None => (s, ""),
+    };
+    let ymd: Vec<&str> = date.split('-').collect();
+    if ymd.len() != 3 {
+        return Err(bad());
+    }
+    let year: i64 = ymd[0].parse().map_err(|_| bad())?;
+    let month: i64 = ymd[1].parse().map_err(|_| bad())?;
+    let day: i64 = ymd[2].parse().map_err(|_| bad())?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(bad());
+    }
+    let mut secs = days_from_civil(year, month, day) * 24 * 60 * 60;
+    if !time.is_empty() {
+        let hms: Vec<&str> = time.split(':').collect();
+        if hms.len() < 2 || hms.len() > 3 {
+            return Err(bad());
+        }
+        let limits = [24, 60, 60];
+        let scales = [60 * 60, 60, 1];
+        for (ii, part) in hms.iter().enumerate() {
+            let n: i64 = part.parse().map_err(|_| bad())?;
+            if n < 0 || n >= limits[ii] {
+                return Err(bad());
+            }
+            secs += n * scales[ii];
+        }
+    }
+    if secs < 0 {
+        return Err(bad());
+    }
+    Ok(UNIX_EPOCH + Duration::from_secs(secs as u64))
+}
+
+// days_in_month is how many days the month has in the Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days_from_civil counts the days from 1970-01-01 to a date in the
+// proleptic Gregorian calendar, using Howard Hinnant's algorithm.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(Size(512), Size::parse("512").unwrap());
+        assert_eq!(Size(64 * 1024), Size::parse("64K").unwrap());
+        assert_eq!(Size(10 * 1024 * 1024), Size::parse("10M").unwrap());
+        assert_eq!(Size(10 * 1024 * 1024), Size::parse("10mb").unwrap());
+        assert_eq!(Size(3 << 29), Size::parse("1.5GiB").unwrap());
+        assert!(Size::parse("ten").is_err());
+        assert!(Size::parse("-1K").is_err());
+        assert!(Size::parse("").is_err());
+    }
+
+    #[test]
+    fn ages() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(Time(now - Duration::from_secs(7200)), Time::parse_at("2h", now).unwrap());
+        assert_eq!(Time(now - Duration::from_secs(36 * 3600)), Time::parse_at("1d12h", now).unwrap());
+        assert_eq!(Time(now - Duration::from_secs(90 * 60)), Time::parse_at("90m", now).unwrap());
+        assert!(Time::parse_at("2", now).is_err());
+        assert!(Time::parse_at("2 hours", now).is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let day = |secs: u64| Time(UNIX_EPOCH + Duration::from_secs(secs));
+        let now = SystemTime::now();
+        assert_eq!(day(0), Time::parse_at("1970-01-01", now).unwrap());
+        assert_eq!(day(1_790_812_800), Time::parse_at("2026-10-01", now).unwrap());
+        assert_eq!(day(1_790_812_800 + 13 * 3600 + 30 * 60), Time::parse_at("2026-10-01T13:30", now).unwrap());
+        assert_eq!(day(951_782_400), Time::parse_at("2000-02-29", now).unwrap());
+        assert!(Time::parse_at("2026-13-01", now).is_err());
+        assert!(Time::parse_at("2026-02-29", now).is_err());
+        assert!(Time::parse_at("2026-02-30", now).is_err());
+        assert!(Time::parse_at("2026-04-31", now).is_err());
+        assert!(Time::parse_at("1900-02-29", now).is_err());
+        assert!(Time::parse_at("2024-02-29", now).is_ok());
+        assert!(Time::parse_at("2026-10-01T25:00", now).is_err());
+        assert!(Time::parse_at("2026-10-0x", now).is_err());
+    }
+}
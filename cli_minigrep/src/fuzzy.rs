@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+// Matcher finds the smallest edit distance between a pattern and any substring
+// of a text.
+//
+// Patterns of up to 64 chars use Myers' bit-parallel algorithm, which keeps a
+// whole column of the edit distance matrix in a pair of u64 bit vectors and so
+// advances one text char per handful of bit operations. Longer patterns fall
+// back to filling in the matrix one cell at a time.
+pub struct Matcher {
+    pattern: Vec<char>,
+    case_sensitive: bool,
+    // peq maps each char in the pattern to a bit mask of the positions it
+    // occurs at.
+    peq: HashMap<char, u64>,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, case_sensitive: bool) -> Matcher {
+        let pattern: Vec<char> = match case_sensitive {
+            true => pattern.chars().collect(),
+            false => pattern.to_lowercase().chars().collect(),
+        };
+        let mut peq = HashMap::new();
+        if pattern.len() <= 64 {
+            for (ii, c) in pattern.iter().enumerate() {
+                *peq.entry(*c).or_insert(0) |= 1 << ii;
+            }
+        }
+        Matcher { pattern, case_sensitive, peq }
+    }
+
+    pub fn distance(&self, text: &str) -> usize {
+        let m = self.pattern.len();
+        if m == 0 {
+            return 0;
+        }
+        let lowered;
+        let text = match self.case_sensitive {
+            true => text,
+            false => {
+                lowered = text.to_lowercase();
+                &lowered
+            }
+        };
+        if m <= 64 {
+            self.myers(text)
+        } else {
+            self.sellers(text)
+        }
+    }
+
+    fn myers(&self, text: &str) -> usize {
+        let m = self.pattern.len();
+        let last = 1u64 << (m - 1);
+        let mut pv = !0u64;
+        let mut mv = 0u64;
+        let mut score = m;
+        let mut best = m;
+        for c in text.chars() {
+            let eq = *self.peq.get(&c).unwrap_or(&0);
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            if ph & last != 0 {
+                score += 1;
+            } else if mh & last != 0 {
+                score -= 1;
+            }
+            // A match may start anywhere in the text, so nothing is carried
+            // into the bottom of the column.
+            ph <<= 1;
+            mh <<= 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+            if score < best {
+                best = score;
+            }
+        }
+        best
+    }
+
+    fn sellers(&self, text: &str) -> usize {
+        let m = self.pattern.len();
+        let mut col: Vec<usize> = (0..m + 1).collect();
+        let mut best = m;
+        for c in text.chars() {
+            let mut diag = col[0];
+            for ii in 1..m + 1 {
+                let cost = if self.pattern[ii - 1] == c { 0 } else { 1 };
+                let next = (diag + cost).min(col[ii] + 1).min(col[ii - 1] + 1);
+                diag = col[ii];
+                col[ii] = next;
+            }
+            if col[m] < best {
+                best = col[m];
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_match_is_zero() {
+        assert_eq!(0, Matcher::new("duct", true).distance("safe, fast, productive."));
+    }
+
+    #[test]
+    fn counts_edits() {
+        let m = Matcher::new("timeout", true);
+        assert_eq!(1, m.distance("connection timout after 5s"));
+        assert_eq!(1, m.distance("connection timeot after 5s"));
+        assert_eq!(2, m.distance("connection tmeot after 5s"));
+    }
+
+    #[test]
+    fn long_patterns_agree_with_myers() {
+        let text = "the quick brown fox jumps over the lazy dog";
+        let short = Matcher::new("quikc brown", true);
+        assert_eq!(short.sellers(text), short.myers(text));
+        let long = "x".repeat(70);
+        assert_eq!(70, Matcher::new(&long, true).distance("abc"));
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{self, IsTerminal};
+use find_all;
+
+const START: &str = "\x1b[1;31m";
+const END: &str = "\x1b[0m";
+
+// When says when to highlight, as --color=auto|always|never.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum When {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    pub fn parse(s: &str) -> Result<When, String> {
+        match s {
+            "auto" => Ok(When::Auto),
+            "always" => Ok(When::Always),
+            "never" => Ok(When::Never),
+            _ => Err(format!("color {} should be auto, always or never", s)),
+        }
+    }
+}
+
+// enabled reports whether highlighting should be used. Left to decide for
+// itself, it only highlights when someone is looking at a terminal, since
+// that's where escape codes are useful; --color=always is for piping into
+// something that understands them, like less -R.
+pub fn enabled(when: When) -> bool {
+    match when {
+        When::Auto => io::stdout().is_terminal(),
+        When::Always => true,
+        When::Never => false,
+    }
+}
+
+// highlight wraps every occurrence of any of terms in line with colour escape
+// codes. Overlapping occurrences are coloured as one.
+pub fn highlight(line: &str, terms: &[&str], case_sensitive: bool) -> String {
+    let mut ranges: Vec<(usize, usize)> = terms.iter()
+        .filter(|term| !term.is_empty())
+        .flat_map(|term| find_all(term, line, case_sensitive))
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(prev) if start <= prev.1 => prev.1 = prev.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut at = 0;
+    for (start, end) in merged {
+        out.push_str(&line[at..start]);
+        out.push_str(START);
+        out.push_str(&line[start..end]);
+        out.push_str(END);
+        at = end;
+    }
+    out.push_str(&line[at..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_each_term() {
+        assert_eq!(
+            "\x1b[1;31mtimeout\x1b[0m talking to \x1b[1;31mDB-primary\x1b[0m",
+            highlight("timeout talking to DB-primary", &["db-primary", "timeout"], false)
+        );
+    }
+
+    #[test]
+    fn overlapping_terms_are_merged() {
+        assert_eq!(
+            "\x1b[1;31mabcd\x1b[0me",
+            highlight("abcde", &["abc", "bcd"], true)
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::convert::TryFrom;
+use serde_json::{self, Value};
+use Config;
+
+// JsonPath picks values out of a JSON document. It's the common subset of
+// JSONPath: a "$" followed by any number of steps.
+// "$.request.user" -> the user field of the request object
+// "$.items[0].id", "$.items[*].id", "$['user name']", "$.tags.*"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    // Any is every member of an object or element of an array.
+    Any,
+}
+
+impl JsonPath {
+    pub fn parse(s: &str) -> Result<JsonPath, String> {
+        let bad = |why: &str| format!("JSON path {} {}", s, why);
+        let mut rest = match s.trim().strip_prefix('$') {
+            Some(rest) => rest,
+            None => return Err(bad("should start with $")),
+        };
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if rest.starts_with("..") {
+                return Err(bad("uses .., which isn't supported"));
+            }
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let key = &after[..end];
+                match key {
+                    "" => return Err(bad("has an empty key")),
+                    "*" => steps.push(Step::Any),
+                    _ => steps.push(Step::Key(key.to_string())),
+                }
+                rest = &after[end..];
+                continue;
+            }
+            let after = match rest.strip_prefix('[') {
+                Some(after) => after,
+                None => return Err(bad("should separate steps with . or [")),
+            };
+            let end = match after.find(']') {
+                Some(end) => end,
+                None => return Err(bad("has an unclosed [")),
+            };
+            let inner = after[..end].trim();
+            let quoted = inner.len() >= 2
+                && (inner.starts_with('\'') && inner.ends_with('\'') || inner.starts_with('"') && inner.ends_with('"'));
+            if inner == "*" {
+                steps.push(Step::Any);
+            } else if quoted {
+                steps.push(Step::Key(inner[1..inner.len() - 1].to_string()));
+            } else {
+                match inner.parse() {
+                    Ok(n) => steps.push(Step::Index(n)),
+                    Err(_) => return Err(bad("should index arrays with numbers or * and quote keys")),
+                }
+            }
+            rest = &after[end + 1..];
+        }
+        Ok(JsonPath { steps })
+    }
+
+    // select returns the values at the path in doc.
+    pub fn select<'a>(&self, doc: &'a Value) -> Vec<&'a Value> {
+        let mut values = vec![doc];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in values {
+                match (step, value) {
+                    (Step::Key(key), Value::Object(map)) => next.extend(map.get(key)),
+                    (Step::Index(ii), Value::Array(items)) => next.extend(items.get(*ii)),
+                    (Step::Any, Value::Object(map)) => next.extend(map.values()),
+                    (Step::Any, Value::Array(items)) => next.extend(items.iter()),
+                    _ => {},
+                }
+            }
+            values = next;
+        }
+        values
+    }
+}
+
+impl TryFrom<String> for JsonPath {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<JsonPath, String> {
+        JsonPath::parse(&s)
+    }
+}
+
+// Hit is a record with a value at the path that matched the query.
+pub struct Hit<'a> {
+    pub line_number: usize,
+    // record is the record as written in the file.
+    pub record: &'a str,
+    // values are the matching values: strings as they are, anything else as
+    // JSON.
+    pub values: Vec<String>,
+}
+
+// Malformed is a record that isn't valid JSON.
+#[derive(Debug, PartialEq)]
+pub struct Malformed {
+    pub line_number: usize,
+    pub err: String,
+}
+
+// search matches the query against the values at --json-path in each record
+// of contents. A file that's a single JSON document, pretty printed or not,
+// is one record starting on line 1. Anything else is read as JSON Lines, one
+// record per line, and lines that don't parse are returned as Malformed
+// rather than stopping the search.
+pub fn search<'a>(cfg: &Config, contents: &'a str) -> (Vec<Hit<'a>>, Vec<Malformed>) {
+    let path = match cfg.json_path {
+        Some(ref path) => path,
+        None => return (Vec::new(), Vec::new()),
+    };
+    let query = match cfg.case_sensitive {
+        true => cfg.query.clone(),
+        false => cfg.query.to_lowercase(),
+    };
+    let mut hits = Vec::new();
+    let mut errors = Vec::new();
+    let mut check = |line_number: usize, record: &'a str, doc: &Value| {
+        let values: Vec<String> = path.select(doc)
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => s.clone(),
+                _ => v.to_string(),
+            })
+            .filter(|v| match cfg.case_sensitive {
+                true => v.contains(&query),
+                false => v.to_lowercase().contains(&query),
+            })
+            .collect();
+        if !values.is_empty() {
+            hits.push(Hit { line_number, record, values });
+        }
+    };
+    if let Ok(doc) = serde_json::from_str::<Value>(contents) {
+        check(1, contents.trim_end(), &doc);
+        return (hits, errors);
+    }
+    for (ii, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(line) {
+            Ok(doc) => check(ii + 1, line, &doc),
+            Err(err) => errors.push(Malformed { line_number: ii + 1, err: err.to_string() }),
+        }
+    }
+    (hits, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = r#"{"request": {"user": "alice", "path": "/"}, "tags": ["a", "b"]}
+{"request": {"user": "bob", "path": "/alice"}}
+
+{"request": oops}
+{"request": {"user": "Alice2", "id": 7}}
+"#;
+
+    fn config(path: &str, query: &str) -> Config {
+        Config { query: query.to_string(), json_path: Some(JsonPath::parse(path).unwrap()), ..Default::default() }
+    }
+
+    #[test]
+    fn parse_paths() {
+        let steps = |s: &str| JsonPath::parse(s).unwrap().steps;
+        assert_eq!(Vec::<Step>::new(), steps("$"));
+        assert_eq!(vec![Step::Key("request".to_string()), Step::Key("user".to_string())], steps("$.request.user"));
+        assert_eq!(vec![Step::Key("items".to_string()), Step::Any, Step::Index(0)], steps("$.items[*][0]"));
+        assert_eq!(vec![Step::Key("user name".to_string()), Step::Any], steps("$['user name'].*"));
+        assert!(JsonPath::parse("request.user").is_err());
+        assert!(JsonPath::parse("$..user").is_err());
+        assert!(JsonPath::parse("$.items[").is_err());
+        assert!(JsonPath::parse("$.items[x]").is_err());
+    }
+
+    #[test]
+    fn matches_values_at_path() {
+        let (hits, errors) = search(&config("$.request.user", "alice"), EVENTS);
+        let lines: Vec<usize> = hits.iter().map(|h| h.line_number).collect();
+        assert_eq!(vec![1, 5], lines);
+        assert_eq!(vec!["Alice2".to_string()], hits[1].values);
+        assert_eq!(1, errors.len());
+        assert_eq!(4, errors[0].line_number);
+
+        let (hits, _) = search(&config("$.request.id", "7"), EVENTS);
+        assert_eq!(5, hits[0].line_number);
+        let (hits, _) = search(&config("$.tags[*]", "b"), EVENTS);
+        assert_eq!(vec!["b".to_string()], hits[0].values);
+    }
+
+    #[test]
+    fn whole_document() {
+        let doc = "[\n  {\"user\": \"alice\"},\n  {\"user\": \"bob\"}\n]\n";
+        let (hits, errors) = search(&config("$[*].user", "bob"), doc);
+        assert!(errors.is_empty());
+        assert_eq!(1, hits[0].line_number);
+        assert_eq!(vec!["bob".to_string()], hits[0].values);
+    }
+}
@@ -1,51 +1,723 @@
+extern crate crossterm;
+extern crate flate2;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[macro_use]
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate tar;
+extern crate zip;
+
+pub mod aggregate;
 pub mod arg;
+pub mod archive;
+pub mod code;
+pub mod completions;
 pub mod config;
+pub mod csv;
+pub mod filter;
+pub mod fuzzy;
+pub mod highlight;
+pub mod jsonpath;
+pub mod literal;
+pub mod multiline;
+pub mod near;
+pub mod pager;
+pub mod passthru;
+pub mod paths;
+pub mod pre;
+pub mod preview;
+pub mod range;
+pub mod server;
+pub mod stats;
+pub mod tui;
+pub mod window;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::error::Error;
+use std::io::{self, BufReader, Cursor, SeekFrom};
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+use aggregate::Aggregate;
 pub use config::Config;
+use range::Section;
+pub use stats::Stats;
+use window::Window;
 
 // Box is a trait object; it allows us to return an object that automatically 
 // satisfies the Error trait without use needing to be concrete about it.
 // This gives us flexibility to return error values that may be of different
 // types in different error cases.
-pub fn run(cfg: Config) -> Result<(), Box<Error>> {
-    let mut f = File::open(cfg.path)?;
+pub fn run(cfg: Config) -> Result<(), Box<dyn Error>> {
+    if cfg.tui && !cfg.prints_info() {
+        return tui::run(cfg);
+    }
+    // Passing stdin through as it's read lets minigrep highlight a stream
+    // that hasn't finished, like tail -f, so it can't wait to page it.
+    let streaming = cfg.passthru && cfg.path == "-" && cfg.lines.is_none() && cfg.bytes.is_none();
+    let mut out = match streaming {
+        true => pager::stdout(&cfg).passthrough(),
+        false => pager::stdout(&cfg),
+    };
+    let written = write_results(&cfg, &mut out, streaming);
+    let finished = out.finish().map_err(Box::from);
+    // Whoever was reading stopped early, say by quitting the pager or
+    // piping into head; that's not an error.
+    match written.and(finished) {
+        Err(ref err) if pager::is_broken_pipe(err.as_ref()) => Ok(()),
+        result => result,
+    }
+}
+
+fn write_results<W: Write>(cfg: &Config, out: &mut W, streaming: bool) -> Result<(), Box<dyn Error>> {
+    if cfg.print_config {
+        for source in &cfg.sources {
+            writeln!(out, "# {}", source)?;
+        }
+        write!(out, "{}", cfg.to_file())?;
+        return Ok(());
+    }
+    if let Some(shell) = cfg.generate_completions {
+        write!(out, "{}", completions::script(shell))?;
+        return Ok(());
+    }
+    if cfg.generate_man {
+        write!(out, "{}", completions::man_page())?;
+        return Ok(());
+    }
+    let mut stats = Stats::default();
+    if streaming {
+        let start = Instant::now();
+        passthru::print(cfg, io::stdin().lock(), out, "", 0, &mut stats)?;
+        stats.files_searched = 1;
+        stats.search = start.elapsed();
+    } else if cfg.aggregate {
+        let mut agg = Aggregate::default();
+        each_input(cfg, &mut stats, |input, stats| {
+            let matches = input.search(cfg);
+            stats.count(&matches);
+            for m in &matches {
+                agg.add(aggregate::normalize(m.line, &cfg.mask));
+            }
+            Ok(())
+        })?;
+        let start = Instant::now();
+        for (count, line) in agg.top(cfg.top.unwrap_or(10)) {
+            writeln!(out, "{:>7} {}", count, line)?;
+        }
+        stats.search += start.elapsed();
+    } else {
+        each_input(cfg, &mut stats, |input, stats| Ok(print_matches(cfg, &input, out, stats)?))?;
+    }
+    if cfg.stats {
+        writeln!(out)?;
+        writeln!(out, "{}", stats)?;
+    }
+    Ok(())
+}
+
+// Input is a piece of text to search: the file at the configured path, or
+// one member of an archive.
+pub struct Input {
+    // path is the file the text came from.
+    pub path: String,
+    // member is the name of the text inside the archive at path.
+    pub member: Option<String>,
+    pub contents: String,
+    // first_line is how many lines of the file come before contents, when
+    // only part of it is being searched.
+    pub first_line: usize,
+}
+
+impl Input {
+    // search finds the matching lines of the input, numbered by where they
+    // are in the whole file.
+    pub fn search(&self, cfg: &Config) -> Vec<Match<'_>> {
+        let mut matches = if cfg.json_path.is_some() {
+            jsonpath::search(cfg, &self.contents).0
+                .into_iter()
+                .map(|hit| Match { line_number: hit.line_number, line: hit.record, distance: None, found: hit.values.len() })
+                .collect()
+        } else if cfg.csv || cfg.tsv {
+            csv::search(cfg, &self.contents)
+        } else if let Some(region) = cfg.region() {
+            let lang = code::Lang::from_path(self.member.as_ref().unwrap_or(&self.path));
+            code::search(cfg, &self.contents, lang, region)
+        } else {
+            search_lines(cfg, &self.contents)
+        };
+        if let Some(window) = Window::new(cfg) {
+            matches = window.keep(&self.contents, matches);
+        }
+        for m in &mut matches {
+            m.line_number += self.first_line;
+        }
+        matches
+    }
+}
+
+// STDIN is how standard input, given as the path "-", is labelled.
+const STDIN: &str = "(standard input)";
+
+// inputs reads everything the config asks to be searched. A directory is
+// walked, and every file under it that passes the metadata filters is
+// searched. Files under it that aren't text are skipped, as are files that
+// can't be read, with a warning.
+pub fn inputs(cfg: &Config) -> Result<Vec<Input>, Box<dyn Error>> {
+    inputs_with_stats(cfg, &mut Stats::default())
+}
+
+// inputs_with_stats is inputs, counting what it reads and skips in stats.
+// Everything is read before it returns; each_input hands over one input at a
+// time instead.
+pub fn inputs_with_stats(cfg: &Config, stats: &mut Stats) -> Result<Vec<Input>, Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    each_input(cfg, stats, |input, _| {
+        inputs.push(input);
+        Ok(())
+    })?;
+    Ok(inputs)
+}
+
+// each_input reads what the config asks to be searched, as inputs does, but
+// calls f with each input as soon as it's read and drops it before reading
+// the next, so a big tree is searched in the memory of its biggest file and
+// results come out while the walk is still going. The time f takes is
+// counted as search time in stats.
+pub fn each_input<F>(cfg: &Config, stats: &mut Stats, mut f: F) -> Result<(), Box<dyn Error>>
+    where F: FnMut(Input, &mut Stats) -> Result<(), Box<dyn Error>>,
+{
+    let start = Instant::now();
+    let (read_before, search_before) = (stats.read, stats.search);
+    let result = find_inputs(cfg, stats, &mut |input, stats| {
+        // A --field name that isn't in a file's header would otherwise just
+        // quietly match nothing.
+        if let Err(err) = csv::column(cfg, &input.contents) {
+            return Err(format!("{}: {}", paths::label(cfg, &input), err).into());
+        }
+        stats.files_searched += 1;
+        stats.bytes_read += input.contents.len() as u64;
+        stats.lines_scanned += input.contents.lines().count();
+        let start = Instant::now();
+        let result = f(input, stats);
+        stats.search += start.elapsed();
+        result
+    });
+    // Time not spent reading or searching files is spent walking.
+    let spent = (stats.read - read_before) + (stats.search - search_before);
+    stats.walk += start.elapsed().saturating_sub(spent);
+    result
+}
+
+// Visit is what find_inputs hands each input it reads to.
+type Visit<'a> = dyn FnMut(Input, &mut Stats) -> Result<(), Box<dyn Error>> + 'a;
+
+fn find_inputs(cfg: &Config, stats: &mut Stats, visit: &mut Visit) -> Result<(), Box<dyn Error>> {
+    if cfg.path == "-" {
+        let start = Instant::now();
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let section = restrict(cfg, Cursor::new(bytes))?;
+        stats.read += start.elapsed();
+        let input = Input {
+            path: STDIN.to_string(),
+            member: None,
+            contents: section.contents,
+            first_line: section.first_line,
+        };
+        return visit(input, stats);
+    }
+    let path = Path::new(&cfg.path);
+    if !path.is_dir() {
+        if !allows(cfg, &cfg.path)? {
+            stats.files_filtered += 1;
+            return Ok(());
+        }
+        let start = Instant::now();
+        let inputs = read_inputs(cfg, &cfg.path, stats);
+        stats.read += start.elapsed();
+        for input in inputs? {
+            visit(input, stats)?;
+        }
+        return Ok(());
+    }
+    walk(path, stats, &mut |file, stats| {
+        let allowed = allows(cfg, &file.to_string_lossy());
+        if let Ok(false) = allowed {
+            stats.files_filtered += 1;
+            return Ok(());
+        }
+        let start = Instant::now();
+        let found = allowed.map_err(|err| err.into()).and_then(|_| read_inputs(cfg, &file.to_string_lossy(), stats));
+        stats.read += start.elapsed();
+        match found {
+            Ok(found) => {
+                for input in found {
+                    visit(input, stats)?;
+                }
+            },
+            Err(ref err) if is_binary(err.as_ref()) => stats.files_binary += 1,
+            Err(ref err) if err.is::<io::Error>() => {
+                eprintln!("{}: {}", file.display(), err);
+                stats.files_errored += 1;
+            },
+            Err(err) => return Err(format!("{}: {}", file.display(), err).into()),
+        }
+        Ok(())
+    })
+}
+
+// VisitFile is what walk hands the path of each file it finds to.
+type VisitFile<'a> = dyn FnMut(PathBuf, &mut Stats) -> Result<(), Box<dyn Error>> + 'a;
+
+// walk calls f with each file under dir, in name order so results come out
+// the same way every time. Symlinks aren't followed, so a link back up the
+// tree can't send the walk round in circles. A directory that can't be read
+// is reported on stderr and counted in stats, and the walk goes on without
+// it; only an error from f stops the walk.
+fn walk(dir: &Path, stats: &mut Stats, f: &mut VisitFile) -> Result<(), Box<dyn Error>> {
+    let mut entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("{}: {}", dir.display(), err);
+            stats.files_errored += 1;
+            return Ok(());
+        },
+    };
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let kind = match entry.file_type() {
+            Ok(kind) => kind,
+            Err(err) => {
+                eprintln!("{}: {}", entry.path().display(), err);
+                stats.files_errored += 1;
+                continue;
+            },
+        };
+        if kind.is_dir() {
+            walk(&entry.path(), stats, f)?;
+        } else if kind.is_file() {
+            f(entry.path(), stats)?;
+        }
+    }
+    Ok(())
+}
+
+// allows reports whether the file at path passes the metadata filters. An
+// archive that's searched member by member is let through, and its members
+// are filtered instead.
+fn allows(cfg: &Config, path: &str) -> io::Result<bool> {
+    let meta = fs::metadata(path)?;
+    let by_member = cfg.archives && archive::Kind::from_path(path).is_some() && !pre::applies(cfg, path);
+    Ok(by_member || filter::allows(cfg, &meta))
+}
+
+// is_binary reports whether err came from reading a file that isn't UTF-8.
+fn is_binary(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::InvalidData)
+}
+
+// read_inputs reads the file at path, or each member of it when it's an
+// archive and --archives is set, leaving out members the metadata filters
+// don't allow. Files the --pre command applies to are read through it
+// instead.
+fn read_inputs(cfg: &Config, path: &str, stats: &mut Stats) -> Result<Vec<Input>, Box<dyn Error>> {
+    if let (Some(ref command), true) = (&cfg.pre, pre::applies(cfg, path)) {
+        let output = match pre::run(command, path) {
+            Ok(output) => output,
+            Err(err) => return Err(format!("--pre: {}", err).into()),
+        };
+        let section = restrict(cfg, Cursor::new(output))?;
+        return Ok(vec![Input {
+            path: path.to_string(),
+            member: None,
+            contents: section.contents,
+            first_line: section.first_line,
+        }]);
+    }
+    if cfg.archives {
+        if let Some(kind) = archive::Kind::from_path(path) {
+            let mut inputs = Vec::new();
+            let mut keep = |size, modified| {
+                let allowed = filter::allows_entry(cfg, size, modified);
+                if !allowed {
+                    stats.files_filtered += 1;
+                }
+                allowed
+            };
+            for m in archive::members(path, &kind, &mut keep)? {
+                let section = match restrict(cfg, Cursor::new(m.contents)) {
+                    Ok(section) => section,
+                    Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+                        stats.files_binary += 1;
+                        continue;
+                    },
+                    Err(err) => return Err(err.into()),
+                };
+                inputs.push(Input {
+                    path: path.to_string(),
+                    member: Some(m.name),
+                    contents: section.contents,
+                    first_line: section.first_line,
+                });
+            }
+            return Ok(inputs);
+        }
+    }
+    let section = restrict(cfg, BufReader::new(File::open(path)?))?;
+    Ok(vec![Input {
+        path: path.to_string(),
+        member: None,
+        contents: section.contents,
+        first_line: section.first_line,
+    }])
+}
+
+// restrict reads the part of r selected by --lines, --bytes or, with
+// --sorted-times, --since and --until, or all of it otherwise.
+fn restrict<R: BufRead + Seek>(cfg: &Config, mut r: R) -> io::Result<Section> {
+    if let Some(ref range) = cfg.lines {
+        return range::read_lines(r, range);
+    }
+    if let Some(ref range) = cfg.bytes {
+        return range::read_bytes(r, range, cfg.needs_line_numbers());
+    }
+    if let (Some(window), true) = (Window::new(cfg), cfg.sorted_times) {
+        return window::read(cfg, &window, r);
+    }
+    r.seek(SeekFrom::Start(0))?;
     let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
-    let lines = match cfg.case_sensitive {
-        false => search_case_insensitive(&cfg.query, &contents),
-        true => search(&cfg.query, &contents),
+    r.read_to_string(&mut contents)?;
+    Ok(Section { contents, first_line: 0 })
+}
+
+// print_matches prints each matching line of the input. Lines from archive
+// members, and from files found by walking a directory, are prefixed with the
+// input's label. With -l only the label is printed, once, if anything
+// matches.
+// Fuzzy matches are annotated with their edit distance: "[1] line".
+// Multiline matches print every line they cover, each prefixed.
+// Proximity matches print as hunks separated by "--", numbering each line
+// grep style: "12:" for lines with a term on them, "12-" for the rest.
+// Other lines are only numbered with --line-number: "12:line".
+// CSV and TSV records print as written, after the header with --header.
+// JSON records always print numbered, "3:record", or with --json-value as
+// "3:value" for each matching value. Malformed records are reported on
+// stderr and skipped.
+fn print_matches<W: Write>(cfg: &Config, input: &Input, out: &mut W, stats: &mut Stats) -> io::Result<()> {
+    let contents = &input.contents;
+    if cfg.vimgrep || cfg.emacs {
+        let matches = input.search(cfg);
+        stats.count(&matches);
+        return print_locations(cfg, input, out, &matches);
+    }
+    if cfg.files_with_matches {
+        let matches = input.search(cfg);
+        stats.count(&matches);
+        if !matches.is_empty() {
+            let end = if cfg.null { '\0' } else { '\n' };
+            write!(out, "{}{}", paths::label(cfg, input), end)?;
+        }
+        return Ok(());
+    }
+    let prefix = match input.member.is_some() || Path::new(&cfg.path).is_dir() {
+        true => format!("{}{}", paths::label(cfg, input), paths::terminator(cfg)),
+        false => String::new(),
     };
-    for line in lines {
-        println!("{}", line);
+    if cfg.passthru {
+        // The input has already been counted as it was read.
+        let mut counted = Stats::default();
+        passthru::print(cfg, contents.as_bytes(), out, &prefix, input.first_line, &mut counted)?;
+        stats.matches += counted.matches;
+        stats.matched_lines += counted.matched_lines;
+        return Ok(());
+    }
+    if cfg.json_path.is_some() {
+        let (hits, errors) = jsonpath::search(cfg, contents);
+        for e in errors {
+            eprintln!("{}:{}: malformed JSON: {}", paths::label(cfg, input), input.first_line + e.line_number, e.err);
+        }
+        stats.matched_lines += hits.len();
+        for hit in hits {
+            stats.matches += hit.values.len();
+            let n = input.first_line + hit.line_number;
+            match cfg.json_value {
+                true => {
+                    for v in &hit.values {
+                        writeln!(out, "{}{}:{}", prefix, n, preview::shorten(cfg, v))?;
+                    }
+                },
+                false => writeln!(out, "{}{}:{}", prefix, n, preview::shorten(cfg, hit.record))?,
+            }
+        }
+        return Ok(());
+    }
+    if let Some(within) = cfg.within {
+        let mut terms = vec![cfg.query.as_str()];
+        terms.extend(cfg.near.iter().map(|t| t.as_str()));
+        let color = highlight::enabled(cfg.color);
+        for (ii, hunk) in near::search_near(&terms, contents, within, cfg.case_sensitive).iter().enumerate() {
+            stats.matches += 1;
+            if ii > 0 {
+                writeln!(out, "--")?;
+            }
+            for (jj, line) in hunk.lines.iter().enumerate() {
+                let found = terms.iter().any(|t| !find_all(t, line, cfg.case_sensitive).is_empty());
+                let sep = if found { ':' } else { '-' };
+                if sep == ':' {
+                    stats.matched_lines += 1;
+                }
+                let line = preview::shorten(cfg, line);
+                let line = if color { highlight::highlight(&line, &terms, cfg.case_sensitive) } else { line.into_owned() };
+                writeln!(out, "{}{}{}{}", prefix, input.first_line + hunk.first + jj + 1, sep, line)?;
+            }
+        }
+        return Ok(());
+    }
+    if cfg.multiline {
+        let query = multiline::unescape(&cfg.query);
+        // line is the number of the line starting at counted. Blocks come in
+        // order, so only the text since the last block needs counting.
+        let mut line = input.first_line + 1;
+        let mut counted = 0;
+        for block in multiline::search_multiline(&query, contents, cfg.case_sensitive) {
+            // Blocks are slices of contents, so where one starts says which
+            // line it starts on.
+            let offset = block.as_ptr() as usize - contents.as_ptr() as usize;
+            line += literal::count_lines(&contents.as_bytes()[counted..offset]);
+            counted = offset;
+            let first = line;
+            stats.matches += 1;
+            stats.matched_lines += block.lines().count();
+            for (ii, line) in block.lines().enumerate() {
+                writeln!(out, "{}{}{}", prefix, line_number(cfg, first + ii), preview::shorten(cfg, line))?;
+            }
+        }
+        return Ok(());
+    }
+    let matches = input.search(cfg);
+    stats.count(&matches);
+    if cfg.header && !matches.is_empty() {
+        if let Some(header) = csv::header(cfg, contents) {
+            writeln!(out, "{}{}{}", prefix, line_number(cfg, header.line_number), preview::shorten(cfg, header.text))?;
+        }
+    }
+    for m in matches {
+        let n = line_number(cfg, m.line_number);
+        match m.distance {
+            Some(d) => writeln!(out, "{}{}[{}] {}", prefix, n, d, preview::shorten(cfg, m.line))?,
+            None => writeln!(out, "{}{}{}", prefix, n, preview::shorten(cfg, m.line))?,
+        }
     }
     Ok(())
 }
 
+// line_number is the "12:" printed before a line with --line-number.
+fn line_number(cfg: &Config, n: usize) -> String {
+    match cfg.line_number {
+        true => format!("{}:", n),
+        false => String::new(),
+    }
+}
+
+// print_locations prints matches in the formats editors read into a list of
+// locations to jump between:
+//
+//     vimgrep: path:line:col:text, once for every match on the line
+//     emacs:   path:line:col: text, once for each matching line
+//
+// Lines and columns count from 1. Vim counts columns in bytes and Emacs in
+// chars. A fuzzy match is reported at column 1, since it doesn't have a
+// single position.
+fn print_locations<W: Write>(cfg: &Config, input: &Input, out: &mut W, matches: &[Match]) -> io::Result<()> {
+    let label = format!("{}{}", paths::label(cfg, input), paths::terminator(cfg));
+    for m in matches {
+        let mut found: Vec<(usize, usize)> = match m.distance {
+            Some(_) => Vec::new(),
+            None => find_all(&cfg.query, m.line, cfg.case_sensitive)
+                .into_iter()
+                .filter(|&(start, end)| end > start)
+                .collect(),
+        };
+        if found.is_empty() {
+            found.push((0, 0));
+        }
+        // With --max-columns only the text is shortened; the columns still
+        // count from the start of the whole line.
+        if cfg.emacs {
+            let col = m.line[..found[0].0].chars().count() + 1;
+            writeln!(out, "{}{}:{}: {}", label, m.line_number, col, preview::shorten_around(cfg, m.line, found[0]))?;
+            continue;
+        }
+        for at in found {
+            writeln!(out, "{}{}:{}:{}", label, m.line_number, at.0 + 1, preview::shorten_around(cfg, m.line, at))?;
+        }
+    }
+    Ok(())
+}
+
+// Match is a line that matched the query.
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    // line_number counts from 1.
+    pub line_number: usize,
+    pub line: &'a str,
+    // distance is the edit distance of a fuzzy match.
+    pub distance: Option<usize>,
+    // found counts the occurrences of the query in the parts of the line
+    // that were searched; a fuzzy match counts as one.
+    pub found: usize,
+}
+
+// search_lines finds the matching lines of contents, honouring the case
+// sensitivity and fuzzy settings of the config.
+pub fn search_lines<'a>(cfg: &Config, contents: &'a str) -> Vec<Match<'a>> {
+    if cfg.case_sensitive && cfg.fuzzy.is_none() && is_literal(&cfg.query) {
+        return literal::matching_lines(&cfg.query, contents)
+            .into_iter()
+            .map(|(line_number, line)| Match { line_number, line, distance: None, found: find_all(&cfg.query, line, true).len() })
+            .collect();
+    }
+    let matcher = cfg.fuzzy.map(|_| fuzzy::Matcher::new(&cfg.query, cfg.case_sensitive));
+    let query = match cfg.case_sensitive {
+        true => cfg.query.clone(),
+        false => cfg.query.to_lowercase(),
+    };
+    contents.lines()
+        .enumerate()
+        .filter_map(|(ii, line)| {
+            let (distance, found) = match (cfg.fuzzy, &matcher) {
+                (Some(max), Some(matcher)) => {
+                    let d = matcher.distance(line);
+                    if d > max {
+                        return None;
+                    }
+                    (Some(d), 1)
+                },
+                _ => {
+                    let found = match cfg.case_sensitive {
+                        true => line.contains(&query),
+                        false => line.to_lowercase().contains(&query),
+                    };
+                    if !found {
+                        return None;
+                    }
+                    (None, find_all(&cfg.query, line, cfg.case_sensitive).len())
+                },
+            };
+            Some(Match { line_number: ii + 1, line, distance, found })
+        })
+        .collect()
+}
 
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    if is_literal(query) {
+        return literal::matching_lines(query, contents).into_iter().map(|(_, line)| line).collect();
+    }
     contents.lines()
         .filter(|line| line.contains(query))
         .collect()
 }
 
-fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+// is_literal reports whether query can be searched for across the whole of
+// the contents at once: it has to be there to find, and can't be split by
+// the line breaks.
+fn is_literal(query: &str) -> bool {
+    !query.is_empty() && !query.contains(['\n', '\r'])
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     let query = query.to_lowercase();
     contents.lines()
         .filter(|line| line.to_lowercase().contains(&query))
         .collect()
 }
 
+// find_all returns the byte range of each non-overlapping match of query.
+pub fn find_all(query: &str, contents: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    if case_sensitive {
+        return contents.match_indices(query)
+            .map(|(ii, m)| (ii, ii + m.len()))
+            .collect();
+    }
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let mut found = Vec::new();
+    let mut from = 0;
+    for (ii, _) in contents.char_indices() {
+        if ii < from {
+            continue;
+        }
+        if let Some(len) = match_len(&contents[ii..], &query) {
+            found.push((ii, ii + len));
+            from = ii + len;
+        }
+    }
+    found
+}
+
+// match_len compares the start of haystack with the lowercased needle, and
+// returns how many bytes of haystack it took to match.
+fn match_len(haystack: &str, needle: &[char]) -> Option<usize> {
+    let mut want = needle.iter();
+    for (ii, c) in haystack.char_indices() {
+        for lc in c.to_lowercase() {
+            match want.next() {
+                Some(w) if *w == lc => {},
+                _ => return None,
+            }
+        }
+        if want.len() == 0 {
+            return Some(ii + c.len_utf8());
+        }
+    }
+    None
+}
+
+// shell makes the command for a command line the user gave, like $PAGER or
+// --pre, followed by args. A line with quotes, variables or other shell
+// syntax is run by sh, the way git and man run one, with args as "$@". A
+// plain one is split into words and run directly, so a missing program is
+// an error starting it rather than a message from sh.
+// "less -R" -> less -R args...
+// "sh -c 'x | y'" -> sh -c "sh -c 'x | y' \"$@\"" sh args...
+fn shell(line: &str, args: &[String]) -> Option<Command> {
+    if line.contains(|c| "|&;<>()$`\\\"'*?[#~=%".contains(c)) {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!("{} \"$@\"", line)).arg("sh").args(args);
+        return Some(cmd);
+    }
+    let mut words = line.split_whitespace();
+    let mut cmd = Command::new(words.next()?);
+    cmd.args(words).args(args);
+    Some(cmd)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn runs_command_lines() {
+        let run = |line: &str| {
+            let output = shell(line, &["a b".to_string()]).unwrap().output().unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_eq!("+a b+", run("printf +%s+"));
+        assert_eq!("it's: a b", run("printf '%s: %s' \"it's\""));
+        assert_eq!("+a b+", run("true | printf +%s+"));
+        assert!(shell(" ", &[]).is_none());
+    }
+
     #[test]
     fn config_not_enough_args() {
         let args: Vec<String> = vec!["one".to_string(), "two".to_string()];
-        if let Ok(_) =  Config::from_args(args) {
+        if Config::from_args(args).is_ok() {
             panic!("wanted error");
         }
     }
@@ -54,10 +726,380 @@ mod tests {
     fn config_enough_args() {
         let args: Vec<String> = vec!["exe".to_string(), "one".to_string(), "two".to_string()];
         if let Err(err) = Config::from_args(args) {
-            panic!(format!("unexpected error: {}", err));
+            panic!("unexpected error: {}", err);
         }
     }
 
+    #[test]
+    fn config_fuzzy() {
+        let args = vec!["exe", "one", "two", "--fuzzy", "2"];
+        let cfg = Config::from_args(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(Some(2), cfg.fuzzy);
+        let args = vec!["exe", "one", "two", "--fuzzy=two"];
+        assert!(Config::from_args(args.into_iter().map(String::from)).is_err());
+    }
+
+    #[test]
+    fn search_lines_numbers_matches() {
+        let mut cfg = Config { query: "t".to_string(), case_sensitive: true, ..Default::default() };
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.";
+        assert_eq!(
+            vec![
+                Match { line_number: 1, line: "Rust:", distance: None, found: 1 },
+                Match { line_number: 2, line: "safe, fast, productive.", distance: None, found: 2 },
+                Match { line_number: 3, line: "Pick three.", distance: None, found: 1 },
+            ],
+            search_lines(&cfg, contents)
+        );
+        cfg.query = "thre".to_string();
+        cfg.fuzzy = Some(1);
+        assert_eq!(
+            vec![Match { line_number: 3, line: "Pick three.", distance: Some(0), found: 1 }],
+            search_lines(&cfg, contents)
+        );
+    }
+
+    #[test]
+    fn search_lines_fuzzy() {
+        let mut cfg = Config { query: "pruductive".to_string(), case_sensitive: true, fuzzy: Some(1), ..Default::default() };
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+        assert_eq!(
+            vec![Match { line_number: 2, line: "safe, fast, productive.", distance: Some(1), found: 1 }],
+            search_lines(&cfg, contents)
+        );
+        cfg.query = "RUST:".to_string();
+        cfg.case_sensitive = false;
+        assert_eq!(
+            vec![
+                Match { line_number: 1, line: "Rust:", distance: Some(0), found: 1 },
+                Match { line_number: 4, line: "Trust me.", distance: Some(1), found: 1 },
+            ],
+            search_lines(&cfg, contents)
+        );
+    }
+
+    #[test]
+    fn ranges_keep_real_line_numbers() {
+        let path = std::env::temp_dir().join(format!("minigrep-ranges-{}", std::process::id()));
+        std::fs::write(&path, "Rust:\nsafe, fast, productive.\nPick three.\nTrust me.\n").unwrap();
+        let mut cfg = Config {
+            query: "t".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            lines: Some(range::Range::parse("3:").unwrap()),
+            ..Default::default()
+        };
+        let found: Vec<usize> = inputs(&cfg).unwrap()[0].search(&cfg).iter().map(|m| m.line_number).collect();
+        assert_eq!(vec![3, 4], found);
+
+        cfg.lines = None;
+        cfg.bytes = Some(range::Range::parse("6:34").unwrap());
+        cfg.line_number = true;
+        let input = inputs(&cfg).unwrap().remove(0);
+        assert_eq!("safe, fast, productive.\nPick", input.contents);
+        let found: Vec<usize> = input.search(&cfg).iter().map(|m| m.line_number).collect();
+        assert_eq!(vec![2], found);
+
+        // JSON records are always numbered, with or without --line-number.
+        std::fs::write(&path, "{\"msg\":\"a\"}\n{\"msg\":\"b\"}\n{\"msg\":\"b\"}\n").unwrap();
+        cfg.query = "b".to_string();
+        cfg.line_number = false;
+        cfg.bytes = Some(range::Range::parse("12:").unwrap());
+        cfg.json_path = Some(jsonpath::JsonPath::parse("$.msg").unwrap());
+        let found: Vec<usize> = inputs(&cfg).unwrap()[0].search(&cfg).iter().map(|m| m.line_number).collect();
+        assert_eq!(vec![2, 3], found);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn walks_directories() {
+        let dir = std::env::temp_dir().join(format!("minigrep-walk-{}", std::process::id()));
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a.log"), "Rust:\n").unwrap();
+        fs::write(dir.join("b").join("c.log"), "Trust me.\n").unwrap();
+        fs::write(dir.join("b").join("big.log"), "Rust ".repeat(100)).unwrap();
+        fs::write(dir.join("image.bin"), [0xff, 0xfe, b'R']).unwrap();
+        let mut cfg = Config {
+            query: "rust".to_string(),
+            path: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let labels = |cfg: &Config| -> Vec<String> {
+            inputs(cfg).unwrap().iter().map(|i| i.path[cfg.path.len() + 1..].to_string()).collect()
+        };
+        assert_eq!(vec!["a.log", "b/big.log", "b/c.log"], labels(&cfg));
+
+        cfg.max_filesize = Some(filter::Size(100));
+        assert_eq!(vec!["a.log", "b/c.log"], labels(&cfg));
+        cfg.max_filesize = None;
+        cfg.min_filesize = Some(filter::Size(100));
+        assert_eq!(vec!["b/big.log"], labels(&cfg));
+        cfg.min_filesize = None;
+        cfg.newer_than = Some(filter::Time::parse("1h").unwrap());
+        assert_eq!(3, labels(&cfg).len());
+        cfg.older_than = Some(filter::Time::parse("2000-01-01").unwrap());
+        cfg.newer_than = None;
+        assert!(labels(&cfg).is_empty());
+
+        cfg.older_than = None;
+        cfg.max_filesize = Some(filter::Size(100));
+        let mut stats = Stats::default();
+        inputs_with_stats(&cfg, &mut stats).unwrap();
+        assert_eq!(2, stats.files_searched);
+        assert_eq!((1, 1, 0), (stats.files_binary, stats.files_filtered, stats.files_errored));
+        assert_eq!(16, stats.bytes_read);
+        assert_eq!(2, stats.lines_scanned);
+
+        // Reading part of each file still skips the ones that aren't text.
+        cfg.lines = Some(range::Range::parse("1:").unwrap());
+        let mut stats = Stats::default();
+        inputs_with_stats(&cfg, &mut stats).unwrap();
+        assert_eq!((2, 1), (stats.files_searched, stats.files_binary));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn marks_near_lines_without_color() {
+        let cfg = Config {
+            query: "rust".to_string(),
+            near: vec!["three".to_string()],
+            within: Some(2),
+            color: highlight::When::Never,
+            ..Default::default()
+        };
+        let input = Input {
+            path: "poem.txt".to_string(),
+            member: None,
+            contents: "Rust:\nsafe, fast, productive.\nPick three.".to_string(),
+            first_line: 0,
+        };
+        let mut out = Vec::new();
+        print_matches(&cfg, &input, &mut out, &mut Stats::default()).unwrap();
+        assert_eq!("1:Rust:\n2-safe, fast, productive.\n3:Pick three.\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn numbers_many_multiline_blocks() {
+        let cfg = Config { query: "foo\\nbar".to_string(), multiline: true, line_number: true, ..Default::default() };
+        let input = Input {
+            path: "many.txt".to_string(),
+            member: None,
+            contents: "foo\nbar\nbaz\n".repeat(100_000),
+            first_line: 10,
+        };
+        let mut out = Vec::new();
+        let mut stats = Stats::default();
+        print_matches(&cfg, &input, &mut out, &mut stats).unwrap();
+        assert_eq!(100_000, stats.matches);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(vec!["11:foo", "12:bar", "14:foo", "15:bar"], lines[..4].to_vec());
+        assert_eq!(vec!["300008:foo", "300009:bar"], lines[lines.len() - 2..].to_vec());
+    }
+
+    #[test]
+    fn counts_only_the_searched_field() {
+        let mut cfg = Config {
+            query: "alice".to_string(),
+            csv: true,
+            field: Some("user".to_string()),
+            stats: true,
+            ..Default::default()
+        };
+        let input = Input {
+            path: "users.csv".to_string(),
+            member: None,
+            contents: "user,note\nalice,alice's alice\nbob,alice\n".to_string(),
+            first_line: 0,
+        };
+        let counted = |cfg: &Config, input: &Input| {
+            let mut stats = Stats::default();
+            print_matches(cfg, input, &mut Vec::new(), &mut stats).unwrap();
+            (stats.matches, stats.matched_lines)
+        };
+        assert_eq!((1, 1), counted(&cfg, &input));
+        cfg.files_with_matches = true;
+        assert_eq!((1, 1), counted(&cfg, &input));
+        cfg.files_with_matches = false;
+        cfg.field = None;
+        assert_eq!((4, 2), counted(&cfg, &input));
+
+        let cfg = Config {
+            query: "alice".to_string(),
+            json_path: Some(jsonpath::JsonPath::parse("$.user").unwrap()),
+            files_with_matches: true,
+            ..Default::default()
+        };
+        let input = Input { contents: "{\"user\": \"alice\", \"alice\": \"alice\"}\n".to_string(), ..input };
+        assert_eq!((1, 1), counted(&cfg, &input));
+    }
+
+    #[test]
+    fn shortens_locations() {
+        let mut cfg = Config { query: "x".to_string(), vimgrep: true, max_columns: Some(6), ..Default::default() };
+        let input = Input {
+            path: "min.js".to_string(),
+            member: None,
+            contents: format!("x{}x", "-".repeat(20)),
+            first_line: 0,
+        };
+        let mut out = Vec::new();
+        print_matches(&cfg, &input, &mut out, &mut Stats::default()).unwrap();
+        assert_eq!(
+            "min.js:1:1:x-----... [16 more bytes]\nmin.js:1:22:...-----x [16 more bytes]\n",
+            String::from_utf8(out).unwrap()
+        );
+        cfg.vimgrep = false;
+        cfg.emacs = true;
+        let mut out = Vec::new();
+        print_matches(&cfg, &input, &mut out, &mut Stats::default()).unwrap();
+        assert_eq!("min.js:1:1: x-----... [16 more bytes]\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn filters_archive_members() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let big = "Rust ".repeat(40);
+        let members: [(&str, &[u8], u64); 4] = [
+            ("old.log", b"Rust\n", 0),
+            ("new.log", b"Rust\n", now),
+            ("big.log", big.as_bytes(), now),
+            ("logo.png", b"\x89PNG\r\n\x1a\n", now),
+        ];
+        for &(name, contents, mtime) in &members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents).unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("minigrep-members-{}.tar", std::process::id()));
+        fs::write(&path, builder.into_inner().unwrap()).unwrap();
+        let cfg = Config {
+            query: "rust".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            archives: true,
+            newer_than: Some(filter::Time::parse("1h").unwrap()),
+            max_filesize: Some(filter::Size(100)),
+            ..Default::default()
+        };
+        let mut stats = Stats::default();
+        let found = inputs_with_stats(&cfg, &mut stats).unwrap();
+        assert_eq!(vec![Some("new.log".to_string())], found.into_iter().map(|i| i.member).collect::<Vec<_>>());
+        assert_eq!((1, 1, 2), (stats.files_searched, stats.files_binary, stats.files_filtered));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn searches_while_walking() {
+        let dir = std::env::temp_dir().join(format!("minigrep-each-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.log"), "Rust:\n").unwrap();
+        fs::write(dir.join("b.log"), "nothing yet\n").unwrap();
+        let cfg = Config { query: "rust".to_string(), path: dir.to_string_lossy().into_owned(), ..Default::default() };
+        // b.log is changed while a.log is being searched, which only shows
+        // up if b.log hasn't been read yet.
+        let mut found = Vec::new();
+        each_input(&cfg, &mut Stats::default(), |input, _| {
+            if input.path.ends_with("a.log") {
+                fs::write(dir.join("b.log"), "Rust, later\n").unwrap();
+            }
+            found.extend(input.search(&cfg).iter().map(|m| m.line.to_string()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(vec!["Rust:", "Rust, later"], found);
+
+        // Stopping early stops the walk.
+        let mut seen = 0;
+        let stopped = each_input(&cfg, &mut Stats::default(), |_, _| {
+            seen += 1;
+            Err("stop".into())
+        });
+        assert_eq!(("stop".to_string(), 1), (stopped.unwrap_err().to_string(), seen));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walks_past_directories_it_cannot_read() {
+        let dir = std::env::temp_dir().join(format!("minigrep-gone-{}", std::process::id()));
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a.log"), "Rust:\n").unwrap();
+        fs::write(dir.join("b/b.log"), "Rust\n").unwrap();
+        fs::write(dir.join("c.log"), "Rust, again\n").unwrap();
+        let cfg = Config { query: "rust".to_string(), path: dir.to_string_lossy().into_owned(), ..Default::default() };
+        // b goes away after the walk has listed it but before reading it.
+        let mut found = Vec::new();
+        let mut stats = Stats::default();
+        each_input(&cfg, &mut stats, |input, _| {
+            if input.path.ends_with("a.log") {
+                fs::remove_dir_all(dir.join("b")).unwrap();
+            }
+            found.extend(input.search(&cfg).iter().map(|m| m.line.to_string()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(vec!["Rust:", "Rust, again"], found);
+        assert_eq!((2, 1), (stats.files_searched, stats.files_errored));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preprocesses_files() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("minigrep-pre-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let upper = script("upper", "tr a-z A-Z");
+        let broken = script("broken", "echo cannot convert \"$1\" >&2; exit 3");
+        let tag = script("tag", "printf '%s: ' \"$1\"; cat");
+        fs::write(dir.join("notes.txt"), "rust\n").unwrap();
+        fs::write(dir.join("report.pdf"), "rust\n").unwrap();
+
+        let mut cfg = Config {
+            query: "RUST".to_string(),
+            case_sensitive: true,
+            path: dir.join("report.pdf").to_string_lossy().into_owned(),
+            pre: Some(upper),
+            pre_glob: vec!["*.pdf".to_string()],
+            ..Default::default()
+        };
+        let found = inputs(&cfg).unwrap();
+        assert_eq!(1, found[0].search(&cfg).len());
+        cfg.path = dir.join("notes.txt").to_string_lossy().into_owned();
+        let found = inputs(&cfg).unwrap();
+        assert!(found[0].search(&cfg).is_empty());
+
+        // Quoted arguments reach the command whole.
+        cfg.query = "plain text: rust".to_string();
+        cfg.path = dir.join("report.pdf").to_string_lossy().into_owned();
+        cfg.pre = Some(format!("{} 'plain text'", tag));
+        let found = inputs(&cfg).unwrap();
+        assert_eq!(1, found[0].search(&cfg).len());
+
+        cfg.pre = Some(broken);
+        cfg.pre_glob.clear();
+        let err = match inputs(&cfg) {
+            Ok(_) => panic!("wanted error"),
+            Err(err) => err.to_string(),
+        };
+        assert!(err.contains("exit status: 3"), "{}", err);
+        assert!(err.contains("cannot convert"), "{}", err);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn one_result() {
         let query = "duct";
@@ -100,4 +1142,37 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    // TEXT makes short texts from a small alphabet, so that queries turn up
+    // often, with CRLF and lone CR line endings thrown in.
+    const TEXT: &str = "[abAB é\r\n]{0,80}";
+
+    proptest! {
+        #[test]
+        fn search_finds_the_lines_with_the_query(query in "[abAé]{1,3}", contents in TEXT) {
+            let naive: Vec<&str> = contents.lines().filter(|line| line.contains(&query)).collect();
+            prop_assert_eq!(naive, search(&query, &contents));
+        }
+
+        #[test]
+        fn case_insensitive_finds_more(query in "[abAB]{1,3}", contents in TEXT) {
+            let insensitive = search_case_insensitive(&query, &contents);
+            for line in search(&query, &contents) {
+                prop_assert!(insensitive.contains(&line), "{:?} is missing {:?}", insensitive, line);
+            }
+        }
+
+        #[test]
+        fn search_lines_numbers_real_lines(query in "[abAé]{1,3}", contents in TEXT, case_sensitive: bool) {
+            let cfg = Config { query, case_sensitive, ..Default::default() };
+            let lines: Vec<&str> = contents.lines().collect();
+            let matches = search_lines(&cfg, &contents);
+            for m in &matches {
+                prop_assert_eq!(lines[m.line_number - 1], m.line);
+                prop_assert!(!find_all(&cfg.query, m.line, case_sensitive).is_empty());
+            }
+            let numbers: Vec<usize> = matches.iter().map(|m| m.line_number).collect();
+            prop_assert!(numbers.windows(2).all(|n| n[0] < n[1]), "{:?}", numbers);
+        }
+    }
 }
\ No newline at end of file
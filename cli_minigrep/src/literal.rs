@@ -0,0 +1,246 @@
+use std::convert::TryInto;
+
+// Finder looks for a literal needle in a haystack of bytes. It scans for the
+// needle's rarest byte first, which is usually far less common than the first
+// byte and lets the scan skip most of the haystack. If that byte turns out to
+// be common in this haystack too, it falls back to Boyer-Moore-Horspool,
+// which skips ahead by up to the needle's length at each step.
+pub struct Finder<'n> {
+    needle: &'n [u8],
+    // rare is where in the needle its rarest byte is.
+    rare: usize,
+    // skip is how far Horspool can move the needle along when the haystack
+    // byte under its last byte is the index.
+    skip: [usize; 256],
+}
+
+// After this many false starts from the rare byte scan, Finder checks
+// whether it's still paying off.
+const PREFILTER_TRIES: usize = 32;
+
+impl<'n> Finder<'n> {
+    pub fn new(needle: &'n [u8]) -> Finder<'n> {
+        let mut rare = 0;
+        for (ii, &b) in needle.iter().enumerate() {
+            if commonness(b) < commonness(needle[rare]) {
+                rare = ii;
+            }
+        }
+        let mut skip = [needle.len(); 256];
+        for (ii, &b) in needle.iter().enumerate().take(needle.len().saturating_sub(1)) {
+            skip[b as usize] = needle.len() - 1 - ii;
+        }
+        Finder { needle, rare, skip }
+    }
+
+    // find returns where the needle first starts in haystack.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let n = self.needle.len();
+        if n == 0 {
+            return Some(0);
+        }
+        if haystack.len() < n {
+            return None;
+        }
+        let last_start = haystack.len() - n;
+        let rare = self.needle[self.rare];
+        let mut pos = 0;
+        let mut tries = 0;
+        loop {
+            let scan = &haystack[pos + self.rare..last_start + self.rare + 1];
+            let start = pos + memchr(rare, scan)?;
+            if &haystack[start..start + n] == self.needle {
+                return Some(start);
+            }
+            pos = start + 1;
+            if pos > last_start {
+                return None;
+            }
+            tries += 1;
+            // Candidates less than a few bytes apart on average mean the
+            // rare byte isn't rare here.
+            if tries >= PREFILTER_TRIES && pos < tries * 8 {
+                return self.horspool(haystack, pos);
+            }
+        }
+    }
+
+    fn horspool(&self, haystack: &[u8], mut pos: usize) -> Option<usize> {
+        let n = self.needle.len();
+        let last = self.needle[n - 1];
+        while pos + n <= haystack.len() {
+            let b = haystack[pos + n - 1];
+            if b == last && haystack[pos..pos + n - 1] == self.needle[..n - 1] {
+                return Some(pos);
+            }
+            pos += self.skip[b as usize];
+        }
+        None
+    }
+}
+
+// commonness guesses how often a byte turns up in text, mostly source code,
+// logs and prose. Only the order matters.
+fn commonness(b: u8) -> u8 {
+    match b {
+        b' ' => 255,
+        b'e' => 250,
+        b't' | b'a' | b'o' | b'i' | b'n' => 240,
+        b's' | b'r' | b'h' | b'l' | b'd' => 230,
+        b'\n' | b'\t' => 220,
+        b'c' | b'u' | b'm' | b'f' | b'p' | b'g' => 210,
+        b'a'..=b'z' => 180,
+        b'0'..=b'9' => 170,
+        b'.' | b',' | b'_' | b'-' | b'(' | b')' | b':' | b';' | b'"' | b'/' | b'=' => 160,
+        b'A'..=b'Z' => 140,
+        0x21..=0x7e => 100,
+        0x80..=0xff => 40,
+        _ => 10,
+    }
+}
+
+// matching_lines finds the lines of contents containing needle, searching
+// the whole of contents for the needle and only then looking for the line
+// breaks around each hit. Lines are numbered from 1 and lose their line
+// break, as with str::lines.
+pub fn matching_lines<'a>(needle: &str, contents: &'a str) -> Vec<(usize, &'a str)> {
+    let finder = Finder::new(needle.as_bytes());
+    let bytes = contents.as_bytes();
+    let mut lines = Vec::new();
+    // line is the number of the line starting at counted.
+    let mut line = 1;
+    let mut counted = 0;
+    let mut pos = 0;
+    while let Some(hit) = finder.find(&bytes[pos..]) {
+        let at = pos + hit;
+        let start = memrchr(b'\n', &bytes[counted..at]).map_or(counted, |ii| counted + ii + 1);
+        line += count_lines(&bytes[counted..start]);
+        counted = start;
+        let from = at + needle.len();
+        let end = memchr(b'\n', &bytes[from..]).map_or(bytes.len(), |ii| from + ii);
+        // Like str::lines, only a CR right before the line break is dropped.
+        let text = &contents[start..end];
+        let text = match end < bytes.len() {
+            true => text.strip_suffix('\r').unwrap_or(text),
+            false => text,
+        };
+        lines.push((line, text));
+        if end == bytes.len() {
+            break;
+        }
+        pos = end + 1;
+    }
+    lines
+}
+
+// count_lines counts the line breaks in bytes, a word at a time.
+pub fn count_lines(bytes: &[u8]) -> usize {
+    let chunks = bytes.chunks_exact(8);
+    let rest = chunks.remainder().iter().filter(|&&b| b == b'\n').count();
+    chunks.map(|chunk| matches(u64::from_le_bytes(chunk.try_into().unwrap()), b'\n').count_ones() as usize).sum::<usize>() + rest
+}
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const LOW7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+
+// matches marks the bytes of word equal to b by setting their top bit, and
+// clears every other bit. Eight bytes are compared at once, without any
+// branches, which is most of what memchr gets from SIMD.
+fn matches(word: u64, b: u8) -> u64 {
+    let x = word ^ (LO * b as u64);
+    // A byte of x is zero where word had b. Adding 0x7f to the low seven
+    // bits of a byte sets its top bit unless they're all zero.
+    !(((x & LOW7) + LOW7) | x | LOW7)
+}
+
+// memchr finds the first b in haystack, a word at a time.
+fn memchr(b: u8, haystack: &[u8]) -> Option<usize> {
+    let mut chunks = haystack.chunks_exact(8);
+    let mut offset = 0;
+    for chunk in &mut chunks {
+        let found = matches(u64::from_le_bytes(chunk.try_into().unwrap()), b);
+        if found != 0 {
+            return Some(offset + found.trailing_zeros() as usize / 8);
+        }
+        offset += 8;
+    }
+    chunks.remainder().iter().position(|&c| c == b).map(|ii| offset + ii)
+}
+
+// memrchr finds the last b in haystack, a word at a time.
+fn memrchr(b: u8, haystack: &[u8]) -> Option<usize> {
+    let mut chunks = haystack.rchunks_exact(8);
+    let mut end = haystack.len();
+    for chunk in &mut chunks {
+        end -= 8;
+        let found = matches(u64::from_le_bytes(chunk.try_into().unwrap()), b);
+        if found != 0 {
+            return Some(end + 7 - found.leading_zeros() as usize / 8);
+        }
+    }
+    chunks.remainder().iter().rposition(|&c| c == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // naive is the obvious search, to check Finder against.
+    fn naive(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        (0..(haystack.len() + 1).saturating_sub(needle.len())).find(|&ii| haystack[ii..].starts_with(needle))
+    }
+
+    #[test]
+    fn finds_like_naive() {
+        let haystack = b"the cat sat on the mat; the rat ate the cat's hat. zebras! eeeeeeeeex";
+        for needle in &["cat", "the", "zebras", "hat.", "x", "eex", "mat;", "dog", "", "the cat sat on the mat; the rat ate the cat's hat. zebras! eeeeeeeeexy"] {
+            let finder = Finder::new(needle.as_bytes());
+            assert_eq!(naive(needle.as_bytes(), haystack), finder.find(haystack), "{}", needle);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_horspool() {
+        // Every byte is the rarest byte of the needle, so the prefilter gives
+        // up and Horspool finishes the job.
+        let mut haystack = vec![b'Q'; 10_000];
+        haystack.extend_from_slice(b"QQQZ");
+        let finder = Finder::new(b"QQZ");
+        assert_eq!(naive(b"QQZ", &haystack), finder.find(&haystack));
+        assert_eq!(None, Finder::new(b"QZQ").find(&haystack));
+    }
+
+    #[test]
+    fn word_at_a_time() {
+        let haystack = b"a\nbc\x80\xff\n0123456789abcdef\nxyz";
+        for &b in &[b'\n', b'a', b'z', 0x80, 0xff, b'q', 0] {
+            for start in 0..haystack.len() {
+                let h = &haystack[start..];
+                assert_eq!(h.iter().position(|&c| c == b), memchr(b, h), "{} from {}", b, start);
+                assert_eq!(h.iter().rposition(|&c| c == b), memrchr(b, h), "{} from {}", b, start);
+                assert_eq!(h.iter().filter(|&&c| c == b'\n').count(), count_lines(h));
+            }
+        }
+    }
+
+    #[test]
+    fn lines_around_hits() {
+        let contents = "Rust:\r\nsafe, fast, productive.\nPick three.\n\nTrust me, rust.";
+        assert_eq!(vec![(1, "Rust:"), (5, "Trust me, rust.")], matching_lines("ust", contents));
+        assert_eq!(vec![(2, "safe, fast, productive."), (3, "Pick three.")], matching_lines("e.", contents));
+        let numbers: Vec<usize> = matching_lines("st", contents).iter().map(|&(n, _)| n).collect();
+        assert_eq!(vec![1, 2, 5], numbers);
+        assert!(matching_lines("nope", contents).is_empty());
+    }
+
+    #[test]
+    fn strips_one_cr_like_lines() {
+        for contents in &["a\r\r\n", "a\r", "a\r\r\na\r", "b\r\na\r\n"] {
+            let expected: Vec<(usize, &str)> =
+                contents.lines().enumerate().filter(|(_, l)| l.contains('a')).map(|(ii, l)| (ii + 1, l)).collect();
+            assert_eq!(expected, matching_lines("a", contents), "{:?}", contents);
+        }
+        assert_eq!(vec![(1, "a\r")], matching_lines("a", "a\r\r\n"));
+        assert_eq!(vec![(1, "a\r")], matching_lines("a", "a\r"));
+    }
+}
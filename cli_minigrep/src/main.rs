@@ -4,7 +4,13 @@ use std::env;
 
 
 fn main() {
-    let cfg = minigrep::Config::from_args(env::args()).unwrap_or_else(|err| {
+    // "minigrep serve" followed by nothing or by the server's options starts
+    // the search server; "minigrep serve notes.txt" still searches for "serve".
+    let args: Vec<String> = env::args().collect();
+    if minigrep::server::is_serve(&args) {
+        return serve(args);
+    }
+    let cfg = minigrep::Config::from_args(args).unwrap_or_else(|err| {
         println!("Could not parse arguments: {}", err);
         process::exit(1);
     });
@@ -12,4 +18,20 @@ fn main() {
         println!("Application error: {}", err);
         process::exit(1);        
     }
-}
\ No newline at end of file
+}
+
+fn serve(args: Vec<String>) {
+    let opts = minigrep::server::Options::from_args(args).unwrap_or_else(|err| {
+        println!("Could not parse arguments: {}", err);
+        process::exit(1);
+    });
+    let server = minigrep::server::Server::bind(&opts).unwrap_or_else(|err| {
+        println!("Could not start server: {}", err);
+        process::exit(1);
+    });
+    println!("Serving {} on http://{}", opts.root, server.local_addr());
+    if let Err(err) = server.run() {
+        println!("Application error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,101 @@
+use find_all;
+
+// search_multiline matches query against the whole of contents rather than
+// line by line, so a query containing newlines can match text spread over
+// several lines. Each result covers every line its match touches; matches
+// that share a line are merged into one result.
+pub fn search_multiline<'a>(query: &str, contents: &'a str, case_sensitive: bool) -> Vec<&'a str> {
+    if query.is_empty() {
+        return contents.lines().collect();
+    }
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in find_all(query, contents, case_sensitive) {
+        let line_start = contents[..start].rfind('\n').map_or(0, |ii| ii + 1);
+        // A match ending with a newline stops at the end of that line rather
+        // than dragging in the line after it.
+        let line_end = if contents[..end].ends_with('\n') {
+            end - 1
+        } else {
+            contents[end..].find('\n').map_or(contents.len(), |ii| end + ii)
+        };
+        match blocks.last_mut() {
+            Some(last) if line_start <= last.1 => {
+                if line_end > last.1 {
+                    last.1 = line_end;
+                }
+            },
+            _ => blocks.push((line_start, line_end)),
+        }
+    }
+    blocks.into_iter()
+        .map(|(start, end)| contents[start..end].trim_end_matches('\r'))
+        .collect()
+}
+
+// unescape lets a query spell out the characters that are awkward to type on a
+// command line.
+// "fn main()\n{" -> "fn main()
+// {"
+pub fn unescape(query: &str) -> String {
+    let mut out = String::with_capacity(query.len());
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            },
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_spans_lines() {
+        let contents = "\
+fn search<'a>(
+    query: &str,
+    contents: &'a str,
+) -> Vec<&'a str> {
+}";
+        assert_eq!(
+            vec!["fn search<'a>(\n    query: &str,"],
+            search_multiline("search<'a>(\n    query", contents, true)
+        );
+    }
+
+    #[test]
+    fn matches_sharing_a_line_are_merged() {
+        let contents = "ax\nyx\nyb\nc";
+        assert_eq!(vec!["ax\nyx\nyb"], search_multiline("x\ny", contents, true));
+    }
+
+    #[test]
+    fn case_insensitive_across_lines() {
+        let contents = "\
+Error: timeout
+  at db-primary
+done";
+        assert_eq!(
+            vec!["Error: timeout\n  at db-primary"],
+            search_multiline("TIMEOUT\n  AT DB", contents, false)
+        );
+    }
+
+    #[test]
+    fn unescapes_newlines() {
+        assert_eq!("a\nb\t\\c\\q", unescape("a\\nb\\t\\\\c\\q"));
+    }
+}
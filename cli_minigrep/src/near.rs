@@ -0,0 +1,97 @@
+// Hunk is a run of consecutive lines in which every search term appears
+// within the configured distance of the others.
+#[derive(Debug, PartialEq)]
+pub struct Hunk<'a> {
+    // first is the zero based index of the first line in the hunk.
+    pub first: usize,
+    pub lines: Vec<&'a str>,
+}
+
+// search_near finds windows of at most within + 1 lines containing every one
+// of terms. Overlapping or touching windows are joined into a single hunk.
+pub fn search_near<'a>(
+    terms: &[&str],
+    contents: &'a str,
+    within: usize,
+    case_sensitive: bool,
+) -> Vec<Hunk<'a>> {
+    let terms: Vec<String> = match case_sensitive {
+        true => terms.iter().map(|t| t.to_string()).collect(),
+        false => terms.iter().map(|t| t.to_lowercase()).collect(),
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    // last holds the most recent line each term was seen on.
+    let mut last: Vec<Option<usize>> = vec![None; terms.len()];
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for (ii, line) in lines.iter().enumerate() {
+        let lowered;
+        let line = match case_sensitive {
+            true => *line,
+            false => {
+                lowered = line.to_lowercase();
+                &lowered
+            }
+        };
+        let mut found = false;
+        for (t, term) in terms.iter().enumerate() {
+            if line.contains(term.as_str()) {
+                last[t] = Some(ii);
+                found = true;
+            }
+        }
+        if !found {
+            continue;
+        }
+        let start = match last.iter().min() {
+            Some(&Some(start)) => start,
+            _ => continue,
+        };
+        if ii - start > within {
+            continue;
+        }
+        match windows.last_mut() {
+            Some(prev) if start <= prev.1 + 1 => prev.1 = ii,
+            _ => windows.push((start, ii)),
+        }
+    }
+    windows.into_iter()
+        .map(|(start, end)| Hunk { first: start, lines: lines[start..end + 1].to_vec() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+connecting to db-primary
+query ok
+query ok
+timeout waiting for reply
+retrying
+query ok
+query ok
+query ok
+timeout waiting for reply";
+
+    #[test]
+    fn terms_within_distance() {
+        assert_eq!(
+            vec![Hunk { first: 0, lines: LOG.lines().take(4).collect() }],
+            search_near(&["timeout", "db-primary"], LOG, 3, true)
+        );
+    }
+
+    #[test]
+    fn terms_too_far_apart() {
+        assert!(search_near(&["timeout", "db-primary"], LOG, 2, true).is_empty());
+    }
+
+    #[test]
+    fn touching_windows_are_joined() {
+        assert_eq!(
+            vec![Hunk { first: 2, lines: LOG.lines().skip(2).take(4).collect() }],
+            search_near(&["TIMEOUT", "Retrying", "query"], LOG, 2, false)
+        );
+    }
+}
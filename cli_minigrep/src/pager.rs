@@ -0,0 +1,212 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Stdio};
+use crossterm::terminal;
+use {shell, Config};
+
+// DEFAULT is the pager used when neither --pager nor $PAGER names one. -R
+// passes colour escapes through.
+const DEFAULT: &str = "less -R";
+
+// Pager is where results are written. Output is held back until there's
+// more of it than fits on the screen; then the pager is started and given
+// everything so far and everything after. If the results fit, or the pager
+// can't be started, they're written to out instead.
+pub struct Pager<W: Write> {
+    out: W,
+    // command is the pager's command line, if paging.
+    command: Option<String>,
+    child: Option<Child>,
+    held: Vec<u8>,
+    // rows counts the screen rows the held output takes up, wrapping lines
+    // at width, and column is how far along the last of them it's got.
+    rows: usize,
+    column: usize,
+    width: usize,
+    height: usize,
+}
+
+// stdout is the pager for a search: the configured pager, when writing to
+// a terminal and not told otherwise, or plain stdout.
+pub fn stdout(cfg: &Config) -> Pager<io::Stdout> {
+    let out = io::stdout();
+    let command = match cfg.no_pager || !out.is_terminal() {
+        true => None,
+        false => command(cfg),
+    };
+    let (width, height) = terminal::size().unwrap_or((80, 24));
+    Pager::new(out, command, width as usize, height as usize)
+}
+
+// command is the pager to run: --pager, then $PAGER, then less -R. An
+// empty one, or cat, means not to page.
+fn command(cfg: &Config) -> Option<String> {
+    let line = match cfg.pager {
+        Some(ref pager) => pager.clone(),
+        None => env::var("PAGER").unwrap_or_else(|_| DEFAULT.to_string()),
+    };
+    match line.split_whitespace().next() {
+        None | Some("cat") => None,
+        Some(_) => Some(line),
+    }
+}
+
+impl<W: Write> Pager<W> {
+    pub fn new(out: W, command: Option<String>, width: usize, height: usize) -> Pager<W> {
+        Pager { out, command, child: None, held: Vec::new(), rows: 0, column: 0, width: width.max(1), height }
+    }
+
+    // passthrough writes straight to out from now on, for output that can't
+    // wait for a screenful, like a stream being passed through.
+    pub fn passthrough(mut self) -> Pager<W> {
+        self.command = None;
+        self
+    }
+
+    // finish writes out anything still held back, then waits for the pager,
+    // if there is one, for the user to quit it.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(mut child) = self.child.take() {
+            // Closing its stdin tells the pager there's no more to come.
+            drop(child.stdin.take());
+            child.wait()?;
+            return Ok(());
+        }
+        self.out.write_all(&self.held)?;
+        self.out.flush()
+    }
+
+    // count adds the rows buf takes up on the screen. Columns are counted in
+    // chars, escapes and all, so colour makes paging start a little early.
+    fn count(&mut self, buf: &[u8]) {
+        for &b in buf {
+            if b == b'\n' {
+                self.rows += 1 + self.column.saturating_sub(1) / self.width;
+                self.column = 0;
+            } else if b & 0xc0 != 0x80 {
+                self.column += 1;
+            }
+        }
+    }
+
+    // start runs the pager and hands it what's been held back. If it can't
+    // be run, minigrep carries on without one.
+    fn start(&mut self) -> io::Result<()> {
+        let line = self.command.take().unwrap_or_default();
+        let spawned = shell(&line, &[]).map(|mut cmd| cmd.stdin(Stdio::piped()).spawn());
+        match spawned {
+            Some(Ok(mut child)) => {
+                let written = match child.stdin {
+                    Some(ref mut stdin) => stdin.write_all(&self.held),
+                    None => Ok(()),
+                };
+                self.child = Some(child);
+                self.held.clear();
+                written
+            },
+            _ => {
+                self.out.write_all(&self.held)?;
+                self.held.clear();
+                Ok(())
+            },
+        }
+    }
+}
+
+impl<W: Write> Write for Pager<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(ref mut child) = self.child {
+            return match child.stdin {
+                Some(ref mut stdin) => stdin.write(buf),
+                None => Ok(buf.len()),
+            };
+        }
+        if self.command.is_none() {
+            return self.out.write(buf);
+        }
+        self.held.extend_from_slice(buf);
+        self.count(buf);
+        // Leave a row for the shell prompt after.
+        if self.rows >= self.height.saturating_sub(1) {
+            self.start()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.child {
+            Some(ref mut child) => child.stdin.as_mut().map_or(Ok(()), |stdin| stdin.flush()),
+            None if self.command.is_some() => Ok(()),
+            None => self.out.flush(),
+        }
+    }
+}
+
+// is_broken_pipe reports whether err is from writing to a reader that's gone
+// away, like a pager the user quit or head, which isn't worth reporting.
+pub fn is_broken_pipe(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(n: usize) -> String {
+        (1..=n).map(|ii| format!("line {}\n", ii)).collect()
+    }
+
+    #[test]
+    fn writes_what_fits() {
+        let mut out = Vec::new();
+        let mut pager = Pager::new(&mut out, Some("no-such-pager".to_string()), 80, 10);
+        pager.write_all(lines(8).as_bytes()).unwrap();
+        pager.finish().unwrap();
+        assert_eq!(lines(8), String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn counts_wrapped_rows() {
+        let mut pager = Pager::new(Vec::new(), None, 10, 24);
+        pager.count("short\n\n0123456789\n01234567890\né".repeat(3).as_bytes());
+        assert_eq!(3 * 5, pager.rows);
+        assert_eq!(1, pager.column);
+    }
+
+    #[test]
+    fn falls_back_without_the_pager() {
+        let mut out = Vec::new();
+        let mut pager = Pager::new(&mut out, Some("no-such-pager".to_string()), 80, 10);
+        pager.write_all(lines(50).as_bytes()).unwrap();
+        pager.finish().unwrap();
+        assert_eq!(lines(50), String::from_utf8(out).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pages_long_output() {
+        // The path has a space in it, which only survives if the quotes
+        // around it are read by a shell.
+        let path = env::temp_dir().join(format!("minigrep pager {}", std::process::id()));
+        let command = format!("cat > '{}'", path.display());
+        let mut out = Vec::new();
+        let mut pager = Pager::new(&mut out, Some(command), 80, 10);
+        for line in lines(50).lines() {
+            writeln!(pager, "{}", line).unwrap();
+        }
+        pager.finish().unwrap();
+        assert!(out.is_empty());
+        assert_eq!(lines(50), std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pager_commands() {
+        let mut cfg = Config { pager: Some("less -RS".to_string()), ..Default::default() };
+        assert_eq!(Some("less -RS".to_string()), command(&cfg));
+        cfg.pager = Some("cat".to_string());
+        assert_eq!(None, command(&cfg));
+        cfg.pager = Some(" ".to_string());
+        assert_eq!(None, command(&cfg));
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, BufRead, Write};
+use {highlight, preview, search_lines, Config, Stats};
+
+// print copies every line of r to out, a line at a time so it can sit in a
+// pipeline reading from a stream that hasn't ended yet. Matching lines are
+// marked with '>' in the gutter and have their matches highlighted:
+//
+//     > 12:timeout talking to db
+//       13-retrying
+//
+// Lines are numbered from first_line + 1 with --line-number, grep style: ':'
+// after the number for a match, '-' otherwise.
+pub fn print<R: BufRead, W: Write>(
+    cfg: &Config,
+    mut r: R,
+    out: &mut W,
+    prefix: &str,
+    first_line: usize,
+    stats: &mut Stats,
+) -> io::Result<()> {
+    let color = highlight::enabled(cfg.color);
+    let terms = [cfg.query.as_str()];
+    let mut buf = String::new();
+    let mut n = first_line;
+    loop {
+        buf.clear();
+        if r.read_line(&mut buf)? == 0 {
+            return Ok(());
+        }
+        n += 1;
+        let line = buf.trim_end_matches('\n').trim_end_matches('\r');
+        let matches = search_lines(cfg, line);
+        let line = preview::shorten(cfg, line);
+        stats.bytes_read += buf.len() as u64;
+        stats.lines_scanned += 1;
+        stats.count(&matches);
+
+        let (mark, sep) = match matches.is_empty() {
+            true => (' ', '-'),
+            false => ('>', ':'),
+        };
+        let number = match cfg.line_number {
+            true => format!("{}{}", n, sep),
+            false => String::new(),
+        };
+        match color && !matches.is_empty() {
+            true => writeln!(out, "{}{} {}{}", prefix, mark, number, highlight::highlight(&line, &terms, cfg.case_sensitive))?,
+            false => writeln!(out, "{}{} {}{}", prefix, mark, number, line)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlight::When;
+
+    const POEM: &str = "Rust:\nsafe, fast, productive.\r\nPick three.\nTrust me.";
+
+    fn passthru(cfg: &Config) -> String {
+        let mut out = Vec::new();
+        print(cfg, POEM.as_bytes(), &mut out, "", 0, &mut Stats::default()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prints_every_line() {
+        let mut cfg = Config { query: "rust".to_string(), color: When::Never, ..Default::default() };
+        assert_eq!("> Rust:\n  safe, fast, productive.\n  Pick three.\n> Trust me.\n", passthru(&cfg));
+
+        cfg.line_number = true;
+        cfg.color = When::Always;
+        assert_eq!(
+            "> 1:\x1b[1;31mRust\x1b[0m:\n  2-safe, fast, productive.\n  3-Pick three.\n> 4:T\x1b[1;31mrust\x1b[0m me.\n",
+            passthru(&cfg)
+        );
+    }
+
+    #[test]
+    fn counts_as_it_goes() {
+        let cfg = Config { query: "e".to_string(), case_sensitive: true, ..Default::default() };
+        let mut stats = Stats::default();
+        print(&cfg, POEM.as_bytes(), &mut io::sink(), "", 0, &mut stats).unwrap();
+        assert_eq!(4, stats.lines_scanned);
+        assert_eq!(POEM.len() as u64, stats.bytes_read);
+        assert_eq!(3, stats.matched_lines);
+        assert_eq!(5, stats.matches);
+    }
+}
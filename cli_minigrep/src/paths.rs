@@ -0,0 +1,120 @@
+use std::env;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use {Config, Input, STDIN};
+
+// label names an input in output the way the config asks: relative to
+// --relative-to, with --path-separator between components.
+// logs/app.log -> "logs/app.log"
+// logs/app.log, --relative-to logs -> "app.log"
+// standard input -> "(standard input)", whatever the config
+// build.tar.gz member docs/a.txt -> "build.tar.gz:docs/a.txt"
+pub fn label(cfg: &Config, input: &Input) -> String {
+    let path = path(cfg, &input.path);
+    match input.member {
+        Some(ref member) => format!("{}:{}", path, member),
+        None => path,
+    }
+}
+
+// terminator is what ends a file name in output: ':' before the rest of the
+// line, or NUL with --null, since NUL is the one byte a path can't contain.
+pub fn terminator(cfg: &Config) -> char {
+    match cfg.null {
+        true => '\0',
+        false => ':',
+    }
+}
+
+// path is label for a path alone, for output that gives an archive member
+// separately, like the search server's.
+pub fn path(cfg: &Config, path: &str) -> String {
+    // Standard input's label isn't a path, so there's nothing to rewrite.
+    if path == STDIN {
+        return path.to_string();
+    }
+    let mut shown = match cfg.relative_to {
+        Some(ref base) => relative(Path::new(path), Path::new(base)).to_string_lossy().into_owned(),
+        None => path.to_string(),
+    };
+    if let Some(ref sep) = cfg.path_separator {
+        shown = shown.replace(MAIN_SEPARATOR, sep);
+    }
+    shown
+}
+
+// relative finds the way to path from base, going up with .. as far as it
+// needs to. Both are taken as they're written, relative to the working
+// directory, without following symlinks.
+// ("logs/app.log", "logs") -> "app.log"
+// ("src/lib.rs", "tests") -> "../src/lib.rs"
+pub fn relative(path: &Path, base: &Path) -> PathBuf {
+    let cwd = env::current_dir().unwrap_or_default();
+    let (path, base) = (cwd.join(path), cwd.join(base));
+    let (path, base) = (normalize(&path), normalize(&base));
+    let common = path.iter().zip(base.iter()).take_while(|(a, b)| a == b).count();
+    let mut rel = PathBuf::new();
+    for _ in common..base.len() {
+        rel.push("..");
+    }
+    for c in &path[common..] {
+        rel.push(c);
+    }
+    if rel.as_os_str().is_empty() {
+        rel.push(".");
+    }
+    rel
+}
+
+// normalize lists the components of an absolute path with any . and ..
+// resolved.
+fn normalize(path: &Path) -> Vec<Component<'_>> {
+    let mut parts = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if let Some(Component::Normal(_)) = parts.last() {
+                    parts.pop();
+                }
+            },
+            _ => parts.push(c),
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(path: &str, member: Option<&str>) -> Input {
+        Input { path: path.to_string(), member: member.map(String::from), contents: String::new(), first_line: 0 }
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(PathBuf::from("app.log"), relative(Path::new("logs/app.log"), Path::new("logs")));
+        assert_eq!(PathBuf::from("app.log"), relative(Path::new("./logs/app.log"), Path::new("logs/")));
+        assert_eq!(PathBuf::from("../src/lib.rs"), relative(Path::new("src/lib.rs"), Path::new("tests")));
+        assert_eq!(PathBuf::from("../../a"), relative(Path::new("/a"), Path::new("/b/c")));
+        assert_eq!(PathBuf::from("."), relative(Path::new("/b/c/.."), Path::new("/b")));
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(PathBuf::from("x/y"), relative(&cwd.join("x/y"), Path::new(".")));
+    }
+
+    #[test]
+    fn labels() {
+        let mut cfg = Config::default();
+        assert_eq!("logs/a b.log", label(&cfg, &input("logs/a b.log", None)));
+        cfg.relative_to = Some("logs".to_string());
+        assert_eq!("a b.log", label(&cfg, &input("logs/a b.log", None)));
+        cfg.relative_to = None;
+        cfg.path_separator = Some("\\".to_string());
+        assert_eq!("build\\x.tar:docs/a.txt", label(&cfg, &input("build/x.tar", Some("docs/a.txt"))));
+        cfg.relative_to = Some("/usr".to_string());
+        assert_eq!(STDIN, label(&cfg, &input(STDIN, None)));
+        assert_eq!(':', terminator(&cfg));
+        cfg.null = true;
+        assert_eq!('\0', terminator(&cfg));
+    }
+}
@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::process::Stdio;
+use {shell, Config};
+
+// applies reports whether the file at path should go through the --pre
+// command: always when no --pre-glob is given, otherwise when one of the
+// globs matches.
+pub fn applies(cfg: &Config, path: &str) -> bool {
+    if cfg.pre.is_none() {
+        return false;
+    }
+    cfg.pre_glob.is_empty() || cfg.pre_glob.iter().any(|g| glob_path(g, path))
+}
+
+// run runs command over the file at path and returns what it printed. The
+// command gets the path as its last argument and the file's contents on
+// stdin, so converters can use whichever suits them. Commands that quote
+// their arguments are run through sh, like --pager.
+// --pre "pdf2txt -q" on q3.pdf -> pdf2txt -q q3.pdf < q3.pdf
+// --pre "conv -t 'plain text'" on q3.pdf -> sh -c "conv -t 'plain text' \"$@\"" sh q3.pdf < q3.pdf
+pub fn run(command: &str, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut cmd = match shell(command, &[path.to_string()]) {
+        Some(cmd) => cmd,
+        None => return Err("--pre command is empty".into()),
+    };
+    let output = cmd
+        .stdin(Stdio::from(File::open(path)?))
+        .stderr(Stdio::piped())
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => return Err(format!("could not run {}: {}", command, err).into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut err = format!("{} {}", command, output.status);
+        if !stderr.trim().is_empty() {
+            err = format!("{}: {}", err, stderr.trim());
+        }
+        return Err(err.into());
+    }
+    Ok(output.stdout)
+}
+
+// glob_path matches a glob against a path. Globs without a '/' only look at
+// the file name, so "*.pdf" matches reports/q3.pdf.
+fn glob_path(glob: &str, path: &str) -> bool {
+    if glob.contains('/') {
+        return glob_match(glob.as_bytes(), path.as_bytes());
+    }
+    let name = Path::new(path).file_name().map_or(path.into(), |n| n.to_string_lossy());
+    glob_match(glob.as_bytes(), name.as_bytes())
+}
+
+// glob_match matches text against a glob where '*' stands for any run of
+// bytes and '?' for any one byte.
+// ("*.pdf", "q3.pdf") -> true
+// ("q?.pdf", "q10.pdf") -> false
+fn glob_match(glob: &[u8], text: &[u8]) -> bool {
+    let (mut g, mut t) = (0, 0);
+    // Where to resume from if the text so far turns out not to fit: just
+    // after the last '*', with it taking one more byte of text.
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                retry = Some((g + 1, t));
+                g += 1;
+                continue;
+            },
+            Some(&c) if c == b'?' || c == text[t] => {
+                g += 1;
+                t += 1;
+                continue;
+            },
+            _ => {},
+        }
+        match retry {
+            Some((rg, rt)) => {
+                g = rg;
+                t = rt + 1;
+                retry = Some((rg, rt + 1));
+            },
+            None => return false,
+        }
+    }
+    glob[g..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_path("*.pdf", "reports/q3.pdf"));
+        assert!(glob_path("q?.pdf", "q3.pdf"));
+        assert!(!glob_path("q?.pdf", "q10.pdf"));
+        assert!(glob_path("reports/*.pb", "reports/a.pb"));
+        assert!(!glob_path("reports/*.pb", "other/a.pb"));
+        assert!(glob_path("*a*b*", "xxaxxbxx"));
+        assert!(!glob_path("*a*b", "xxaxxbxx"));
+        assert!(glob_path("*", ""));
+    }
+
+    #[test]
+    fn applies_to_globbed_files() {
+        let mut cfg = Config { pre: Some("cat".to_string()), ..Default::default() };
+        assert!(applies(&cfg, "notes.txt"));
+        cfg.pre_glob.push("*.pdf".to_string());
+        assert!(!applies(&cfg, "notes.txt"));
+        assert!(applies(&cfg, "q3.pdf"));
+        cfg.pre = None;
+        assert!(!applies(&cfg, "q3.pdf"));
+    }
+}
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+use {find_all, Config};
+
+// shorten cuts a line longer than --max-columns bytes down to a preview of
+// --max-columns-preview bytes (--max-columns unless it's set), centred on
+// the first match of the query so the match is still on screen:
+//
+//     ...,"user":"alice","status":500,"path":"/api... [48213 more bytes]
+//
+// Lines that fit are left alone. A line the query can't be found on as
+// written, like a fuzzy match, is previewed from its start.
+pub fn shorten<'a>(cfg: &Config, line: &'a str) -> Cow<'a, str> {
+    let first = find_all(&cfg.query, line, cfg.case_sensitive)
+        .into_iter()
+        .find(|&(start, end)| end > start)
+        .unwrap_or((0, 0));
+    shorten_around(cfg, line, first)
+}
+
+// shorten_around is shorten, centred on the match from start to end instead,
+// for output that reports each match on a line separately, like --vimgrep.
+pub fn shorten_around<'a>(cfg: &Config, line: &'a str, (start, end): (usize, usize)) -> Cow<'a, str> {
+    let max = match cfg.max_columns {
+        Some(max) if line.len() > max => max,
+        _ => return Cow::Borrowed(line),
+    };
+    let width = cfg.max_columns_preview.unwrap_or(max).min(line.len());
+    let centre = start + (end - start) / 2;
+    let mut from = centre.saturating_sub(width / 2).min(line.len() - width);
+    let mut to = from + width;
+    while !line.is_char_boundary(from) {
+        from -= 1;
+    }
+    while !line.is_char_boundary(to) {
+        to -= 1;
+    }
+    let before = if from > 0 { "..." } else { "" };
+    let after = if to < line.len() { "..." } else { "" };
+    let omitted = line.len() - (to - from);
+    Cow::Owned(format!("{}{}{} [{} more bytes]", before, &line[from..to], after, omitted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(query: &str, max: usize, preview: Option<usize>) -> Config {
+        Config { query: query.to_string(), max_columns: Some(max), max_columns_preview: preview, ..Default::default() }
+    }
+
+    #[test]
+    fn leaves_short_lines() {
+        assert_eq!("short line", shorten(&cfg("line", 10, None), "short line"));
+        assert_eq!("any length", shorten(&Config::default(), "any length"));
+    }
+
+    #[test]
+    fn centres_on_the_match() {
+        let line = "aaaaaaaaaa needle bbbbbbbbbb";
+        assert_eq!("...a needle b... [18 more bytes]", shorten(&cfg("needle", 20, Some(10)), line));
+        assert_eq!("...a needle b... [18 more bytes]", shorten(&cfg("NEEDLE", 20, Some(10)), line));
+        assert_eq!("...bbbbbbbbbb [18 more bytes]", shorten(&cfg("bbbbbbbbbb", 20, Some(10)), line));
+        assert_eq!("aaaaaaaaaa... [18 more bytes]", shorten(&Config { fuzzy: Some(1), ..cfg("nedle", 20, Some(10)) }, line));
+        assert_eq!("aaaaaaaaaa needle bb... [8 more bytes]", shorten(&cfg("a", 20, None), line));
+        assert_eq!("...bbbbbbbbbb [18 more bytes]", shorten_around(&cfg("b", 20, Some(10)), line, (27, 28)));
+    }
+
+    #[test]
+    fn keeps_whole_chars() {
+        let line = "ééééé needle ééééé";
+        let shown = shorten(&cfg("needle", 10, Some(9)), line);
+        assert_eq!("... needle ... [20 more bytes]", shown);
+    }
+}
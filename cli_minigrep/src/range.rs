@@ -0,0 +1,185 @@
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+// Range is a span written START:END, where either end can be left off.
+// --lines counts from 1 and includes both ends; --bytes counts from 0 and
+// stops just before END, the way slices do.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Range {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl Range {
+    // "1000:2000" -> Range { start: Some(1000), end: Some(2000) }
+    // "4096:" -> Range { start: Some(4096), end: None }
+    pub fn parse(s: &str) -> Result<Range, String> {
+        let mut parts = s.splitn(2, ':');
+        let (start, end) = match (parts.next(), parts.next()) {
+            (Some(start), Some(end)) => (start.trim(), end.trim()),
+            _ => return Err(format!("range {} should look like START:END", s)),
+        };
+        let bound = |b: &str| -> Result<Option<u64>, String> {
+            if b.is_empty() {
+                return Ok(None);
+            }
+            match b.parse() {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => Err(format!("range {} should be made of numbers", s)),
+            }
+        };
+        let range = Range { start: bound(start)?, end: bound(end)? };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if start > end {
+                return Err(format!("range {} ends before it starts", s));
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl TryFrom<String> for Range {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Range, String> {
+        Range::parse(&s)
+    }
+}
+
+// Section is the part of a file a range selects.
+pub struct Section {
+    pub contents: String,
+    // first_line is how many lines come before the section, so that line
+    // numbers within it can be turned back into line numbers in the file.
+    pub first_line: usize,
+}
+
+// read_lines reads the lines of r that fall within range. The lines before
+// it are read one at a time and dropped, and reading stops once the range
+// is over.
+pub fn read_lines<R: BufRead>(mut r: R, range: &Range) -> io::Result<Section> {
+    let first = range.start.unwrap_or(1).max(1);
+    let mut buf = Vec::new();
+    let mut line = 1;
+    while line < first {
+        buf.clear();
+        if r.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line += 1;
+    }
+    let first_line = (line - 1) as usize;
+    buf.clear();
+    while range.end.is_none_or(|end| line <= end) {
+        if r.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line += 1;
+    }
+    Ok(Section { contents: text(buf, false)?, first_line })
+}
+
+// read_bytes reads the bytes of r that fall within range, seeking straight
+// to the start. The bytes skipped over are only read when count_lines is set,
+// a chunk at a time, to count the line breaks in them.
+//
+// A range can start or end part way through a char; the pieces of it are
+// replaced with U+FFFD. Anything else that isn't UTF-8 is an error, as it
+// would be reading the whole file.
+pub fn read_bytes<R: Read + Seek>(mut r: R, range: &Range, count_lines: bool) -> io::Result<Section> {
+    let start = range.start.unwrap_or(0);
+    let mut first_line = 0;
+    if count_lines {
+        let mut chunk = [0; 64 * 1024];
+        let mut left = start;
+        while left > 0 {
+            let want = chunk.len().min(left as usize);
+            let n = r.read(&mut chunk[..want])?;
+            if n == 0 {
+                break;
+            }
+            first_line += chunk[..n].iter().filter(|&&b| b == b'\n').count();
+            left -= n as u64;
+        }
+    } else {
+        r.seek(SeekFrom::Start(start))?;
+    }
+    let mut buf = Vec::new();
+    match range.end {
+        Some(end) => r.take(end - start).read_to_end(&mut buf)?,
+        None => r.read_to_end(&mut buf)?,
+    };
+    Ok(Section { contents: text(buf, true)?, first_line })
+}
+
+// text checks the bytes read are UTF-8, failing with InvalidData the way
+// read_to_string does, so a file that isn't text is skipped whether it's read
+// whole or in part. With cut set, a char cut off at either end is allowed.
+fn text(buf: Vec<u8>, cut: bool) -> io::Result<String> {
+    let lead = match cut {
+        true => buf.iter().take(3).take_while(|&&b| b & 0xc0 == 0x80).count(),
+        false => 0,
+    };
+    match std::str::from_utf8(&buf[lead..]) {
+        Ok(_) => {},
+        Err(ref err) if cut && err.error_len().is_none() => {},
+        Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")),
+    }
+    Ok(String::from_utf8(buf).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const TEXT: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(Range { start: Some(1000), end: Some(2000) }, Range::parse("1000:2000").unwrap());
+        assert_eq!(Range { start: Some(4096), end: None }, Range::parse("4096:").unwrap());
+        assert_eq!(Range { start: None, end: Some(10) }, Range::parse(":10").unwrap());
+        assert!(Range::parse("10").is_err());
+        assert!(Range::parse("a:b").is_err());
+        assert!(Range::parse("20:10").is_err());
+    }
+
+    #[test]
+    fn lines_in_range() {
+        let section = read_lines(Cursor::new(TEXT), &Range::parse("2:3").unwrap()).unwrap();
+        assert_eq!("two\nthree\n", section.contents);
+        assert_eq!(1, section.first_line);
+
+        let section = read_lines(Cursor::new(TEXT), &Range::parse("4:").unwrap()).unwrap();
+        assert_eq!("four\nfive\n", section.contents);
+        assert_eq!(3, section.first_line);
+
+        let section = read_lines(Cursor::new(TEXT), &Range::parse("9:").unwrap()).unwrap();
+        assert_eq!("", section.contents);
+    }
+
+    #[test]
+    fn bytes_in_range() {
+        let section = read_bytes(Cursor::new(TEXT), &Range::parse("8:18").unwrap(), true).unwrap();
+        assert_eq!("three\nfour", section.contents);
+        assert_eq!(2, section.first_line);
+
+        let section = read_bytes(Cursor::new(TEXT), &Range::parse("19:").unwrap(), false).unwrap();
+        assert_eq!("five\n", section.contents);
+        assert_eq!(0, section.first_line);
+    }
+
+    #[test]
+    fn only_reads_text() {
+        let range = Range::parse("1:5").unwrap();
+        assert_eq!("\u{fffd}ab\u{fffd}", read_bytes(Cursor::new("éabé"), &range, false).unwrap().contents);
+        let binary: &[u8] = b"one\ntw\xff\x00\nthree";
+        let err = read_bytes(Cursor::new(binary), &Range::parse("4:").unwrap(), false).err();
+        assert_eq!(Some(io::ErrorKind::InvalidData), err.map(|e| e.kind()));
+        let err = read_lines(Cursor::new(binary), &Range::parse("2:").unwrap()).err();
+        assert_eq!(Some(io::ErrorKind::InvalidData), err.map(|e| e.kind()));
+        assert!(read_lines(Cursor::new(binary), &Range::parse("3:").unwrap()).is_ok());
+    }
+}
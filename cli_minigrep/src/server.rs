@@ -0,0 +1,400 @@
+use std::error::Error;
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use serde_json;
+use config::Opt;
+use {each_input, paths, Config, Stats};
+
+// The largest request body we'll read; queries are small.
+const MAX_BODY: usize = 1 << 20;
+
+// Options configures `minigrep serve`.
+#[derive(Debug)]
+pub struct Options {
+    pub root: String,
+    pub listen: String,
+}
+
+// OPTIONS lists the options of `minigrep serve`, for the completions and man
+// page.
+pub const OPTIONS: &[Opt] = &[
+    Opt { long: "root", short: None, value: Some("DIR"), repeatable: false, help: "serve searches over the files under DIR (default .)" },
+    Opt { long: "listen", short: None, value: Some("ADDR"), repeatable: false, help: "listen on ADDR (default 127.0.0.1:7878)" },
+];
+
+// is_serve reports whether args ask for the search server: "minigrep serve"
+// on its own or followed by the server's options. Anything else after it is
+// a search for "serve": "minigrep serve -", "minigrep serve -U notes.txt".
+pub fn is_serve(args: &[String]) -> bool {
+    if args.get(1).map(String::as_str) != Some("serve") {
+        return false;
+    }
+    match args.get(2) {
+        Some(arg) => matches!(arg.split('=').next(), Some("--root") | Some("--listen")),
+        None => true,
+    }
+}
+
+impl Options {
+    // minigrep serve --root logs --listen 127.0.0.1:7878
+    pub fn from_args<I>(args: I) -> Result<Options, String>
+        where I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().skip(2);
+        let mut opts = Options { root: ".".to_string(), listen: "127.0.0.1:7878".to_string() };
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.find('=') {
+                Some(ii) => (&arg[..ii], Some(arg[ii + 1..].to_string())),
+                None => (arg.as_str(), None),
+            };
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(format!("argument {} needs a value", arg)),
+            };
+            match name {
+                "--root" => opts.root = value,
+                "--listen" => opts.listen = value,
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+// Server answers search requests for files and directories under root:
+//
+//     POST /search
+//     {"query": "timeout", "path": "app.log", "case_sensitive": true}
+//
+// The body takes the same fields as Config. Matches stream back as JSON
+// lines, one object per matching line:
+//
+//     {"distance":null,"line":"...","line_number":3,"member":null,"path":"app.log"}
+//
+// With "stats": true, a last line sums up the search: {"stats": {...}}.
+//
+// Requests that can't be served get a JSON {"error": "..."} body instead.
+pub struct Server {
+    listener: TcpListener,
+    root: PathBuf,
+}
+
+impl Server {
+    pub fn bind(opts: &Options) -> Result<Server, Box<dyn Error>> {
+        let root = match fs::canonicalize(&opts.root) {
+            Ok(root) => root,
+            Err(err) => return Err(format!("root {}: {}", opts.root, err).into()),
+        };
+        let listener = TcpListener::bind(&opts.listen)?;
+        Ok(Server { listener, root })
+    }
+
+    pub fn local_addr(&self) -> String {
+        self.listener.local_addr().map(|a| a.to_string()).unwrap_or_default()
+    }
+
+    // run handles connections, each on its own thread, until the listener
+    // fails.
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let root = self.root.clone();
+            thread::spawn(move || {
+                // The client hanging up part way through isn't our problem.
+                let _ = handle(stream, &root);
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle(mut stream: TcpStream, root: &Path) -> Result<(), Box<dyn Error>> {
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(err) => return respond_error(&mut stream, "400 Bad Request", &err),
+    };
+    if request.path != "/search" {
+        return respond_error(&mut stream, "404 Not Found", "not found");
+    }
+    if request.method != "POST" {
+        return respond_error(&mut stream, "405 Method Not Allowed", "use POST");
+    }
+    let cfg = match query_config(&request.body, root) {
+        Ok(cfg) => cfg,
+        Err(err) => return respond_error(&mut stream, "400 Bad Request", &err),
+    };
+    let mut stats = Stats::default();
+    // The headers go out with the first results, so a file that can't be
+    // searched at all still gets a 400.
+    let mut started = false;
+    let searched = each_input(&cfg, &mut stats, |input, stats| {
+        if !started {
+            write_headers(&mut stream)?;
+            started = true;
+        }
+        let matches = input.search(&cfg);
+        stats.count(&matches);
+        for m in matches {
+            let hit = json!({
+                "path": paths::path(&cfg, &input.path),
+                "member": input.member,
+                "line_number": m.line_number,
+                "line": m.line,
+                "distance": m.distance,
+            });
+            write_chunk(&mut stream, &format!("{}\n", hit))?;
+        }
+        Ok(())
+    });
+    match searched {
+        Err(err) if !started => return respond_error(&mut stream, "400 Bad Request", &err.to_string()),
+        Err(err) => write_chunk(&mut stream, &format!("{}\n", json!({ "error": err.to_string() })))?,
+        Ok(()) if !started => write_headers(&mut stream)?,
+        Ok(()) => {},
+    }
+    if cfg.stats {
+        write_chunk(&mut stream, &format!("{}\n", json!({ "stats": stats.to_json() })))?;
+    }
+    write_chunk(&mut stream, "")?;
+    stream.flush()?;
+    Ok(())
+}
+
+// query_config turns a request body into a Config, and checks the path it
+// asks for is a file under root.
+fn query_config(body: &[u8], root: &Path) -> Result<Config, String> {
+    let mut cfg: Config = match serde_json::from_slice(body) {
+        Ok(cfg) => cfg,
+        Err(err) => return Err(format!("invalid query: {}", err)),
+    };
+    if cfg.multiline || !cfg.near.is_empty() || cfg.vimgrep || cfg.emacs {
+        return Err("multiline, near, vimgrep and emacs aren't supported by the server".to_string());
+    }
+    cfg.validate()?;
+    // Results always carry line numbers, so ranges need to count them.
+    cfg.line_number = true;
+    cfg.path = resolve(root, &cfg.path)?.to_string_lossy().into_owned();
+    // Paths in results are given relative to the root, the way they were
+    // asked for.
+    cfg.relative_to = Some(root.to_string_lossy().into_owned());
+    Ok(cfg)
+}
+
+// resolve finds path within root, rejecting any path that would lead outside
+// of it, whether by "..", by being absolute or by following a symlink.
+fn resolve(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    let escapes = relative.components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if path.is_empty() || escapes {
+        return Err(format!("path {} must be relative to the root, without ..", path));
+    }
+    let full = match fs::canonicalize(root.join(relative)) {
+        Ok(full) => full,
+        Err(err) => return Err(format!("path {}: {}", path, err)),
+    };
+    if !full.starts_with(root) {
+        return Err(format!("path {} is outside the root", path));
+    }
+    // A directory is walked the way the command line walks one, so nothing
+    // under it can lead back outside the root either.
+    if !full.is_file() && !full.is_dir() {
+        return Err(format!("path {} is not a file or directory", path));
+    }
+    Ok(full)
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+// read_request reads just enough HTTP/1.1 to get a request's method, path
+// and body.
+fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if let Err(err) = reader.read_line(&mut line) {
+        return Err(err.to_string());
+    }
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err("malformed request line".to_string()),
+    };
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) => return Err("unexpected end of headers".to_string()),
+            Ok(_) => {},
+            Err(err) => return Err(err.to_string()),
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let mut kv = header.splitn(2, ':');
+        let name = kv.next().unwrap_or("");
+        let value = kv.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = match value.parse() {
+                Ok(length) => length,
+                Err(_) => return Err(format!("bad Content-Length {}", value)),
+            };
+        }
+    }
+    if length > MAX_BODY {
+        return Err("request body too large".to_string());
+    }
+    let mut body = vec![0; length];
+    if let Err(err) = reader.read_exact(&mut body) {
+        return Err(err.to_string());
+    }
+    Ok(Request { method, path, body })
+}
+
+fn respond_error(stream: &mut TcpStream, status: &str, err: &str) -> Result<(), Box<dyn Error>> {
+    let body = json!({ "error": err }).to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status, body.len(), body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn write_headers(stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/x-ndjson\r\n\
+         Transfer-Encoding: chunked\r\n\
+         Connection: close\r\n\r\n"
+    )?;
+    Ok(())
+}
+
+fn write_chunk(stream: &mut TcpStream, data: &str) -> Result<(), Box<dyn Error>> {
+    write!(stream, "{:x}\r\n{}\r\n", data.len(), data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::net::Shutdown;
+
+    // serve starts a server over a fresh root holding poem.txt, and returns
+    // the root and the address it's listening on.
+    fn serve(name: &str) -> (PathBuf, String) {
+        let root = env::temp_dir().join(format!("minigrep-serve-{}-{}", std::process::id(), name));
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("logs").join("poem.txt"), "Rust:\nsafe, fast, productive.\nTrust me.").unwrap();
+        let opts = Options { root: root.to_string_lossy().into_owned(), listen: "127.0.0.1:0".to_string() };
+        let server = Server::bind(&opts).unwrap();
+        let addr = server.local_addr();
+        thread::spawn(move || {
+            let _ = server.run();
+        });
+        (root, addr)
+    }
+
+    fn post(addr: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn options_from_args() {
+        let args = vec!["minigrep", "serve", "--root", "logs", "--listen=127.0.0.1:9000"];
+        let opts = Options::from_args(args.into_iter().map(String::from)).unwrap();
+        assert_eq!("logs", opts.root);
+        assert_eq!("127.0.0.1:9000", opts.listen);
+        assert!(Options::from_args(vec!["minigrep".to_string(), "serve".to_string(), "--port".to_string(), "1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn serve_or_search() {
+        let is = |args: &[&str]| is_serve(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+        assert!(is(&["minigrep", "serve"]));
+        assert!(is(&["minigrep", "serve", "--root", "logs"]));
+        assert!(is(&["minigrep", "serve", "--listen=127.0.0.1:9000"]));
+        assert!(!is(&["minigrep", "serve", "-"]));
+        assert!(!is(&["minigrep", "serve", "--match-case", "notes.txt"]));
+        assert!(!is(&["minigrep", "serve", "notes.txt"]));
+        assert!(!is(&["minigrep", "--root", "serve"]));
+    }
+
+    #[test]
+    fn streams_matches() {
+        let (root, addr) = serve("matches");
+        let response = post(&addr, "/search", r#"{"query": "rust", "path": "logs/poem.txt"}"#);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Transfer-Encoding: chunked"));
+        assert!(response.contains(r#"{"distance":null,"line":"Rust:","line_number":1,"member":null,"path":"logs/poem.txt"}"#));
+        assert!(response.contains(r#""line":"Trust me.","line_number":3"#));
+        assert!(!response.contains("productive"));
+        assert!(!response.contains("stats"));
+        assert!(response.ends_with("0\r\n\r\n"));
+
+        let response = post(&addr, "/search", r#"{"query": "rust", "path": "logs/poem.txt", "stats": true}"#);
+        assert!(response.contains(r#"{"stats":{"bytes_read":39,"#), "{}", response);
+        assert!(response.contains(r#""files_searched":1,"#), "{}", response);
+        assert!(response.contains(r#""lines_scanned":3,"matched_lines":2,"matches":2}}"#), "{}", response);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn searches_directories() {
+        let (root, addr) = serve("directory");
+        fs::write(root.join("logs").join("app.log"), "trusted\n").unwrap();
+        let response = post(&addr, "/search", r#"{"query": "rust", "path": "logs"}"#);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        let app = response.find(r#""line":"trusted","line_number":1,"member":null,"path":"logs/app.log""#);
+        let poem = response.find(r#""line":"Rust:","line_number":1,"member":null,"path":"logs/poem.txt""#);
+        assert!(app.is_some() && poem.is_some() && app < poem, "{}", response);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_outside_root() {
+        let (root, addr) = serve("traversal");
+        let secret = root.with_extension("secret");
+        fs::write(&secret, "Rust secret").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&secret, root.join("logs").join("link")).unwrap();
+        let outside = format!("../{}", secret.file_name().unwrap().to_string_lossy());
+        for path in &[outside.as_str(), "logs/../../x", secret.to_str().unwrap(), "logs/link"] {
+            let body = format!(r#"{{"query": "Rust", "path": "{}"}}"#, path);
+            let response = post(&addr, "/search", &body);
+            assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+            assert!(!response.contains("Rust secret"), "{}", response);
+        }
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_file(secret).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let (root, addr) = serve("bad");
+        assert!(post(&addr, "/search", "{").starts_with("HTTP/1.1 400"));
+        assert!(post(&addr, "/search", r#"{"query": "a", "path": "logs/poem.txt", "colour": 1}"#).starts_with("HTTP/1.1 400"));
+        assert!(post(&addr, "/elsewhere", "{}").starts_with("HTTP/1.1 404"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::time::Duration;
+use serde_json::Value;
+use Match;
+
+// Stats sums up a search: what was looked at, what was found and where the
+// time went.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub files_searched: usize,
+    // Files that were skipped, by why: not text, left out by the metadata
+    // filters, or unreadable.
+    pub files_binary: usize,
+    pub files_filtered: usize,
+    pub files_errored: usize,
+    pub bytes_read: u64,
+    pub lines_scanned: usize,
+    // matches counts each occurrence of the query; matched_lines counts the
+    // lines (or records) they're on.
+    pub matches: usize,
+    pub matched_lines: usize,
+    // walk is the time spent finding files, read reading them and search
+    // matching and printing them.
+    pub walk: Duration,
+    pub read: Duration,
+    pub search: Duration,
+}
+
+impl Stats {
+    pub fn files_skipped(&self) -> usize {
+        self.files_binary + self.files_filtered + self.files_errored
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.walk + self.read + self.search
+    }
+
+    // count adds matching lines to the totals, with the occurrences each
+    // search mode found on them. Every line counts as at least one match.
+    pub fn count(&mut self, matches: &[Match]) {
+        self.matched_lines += matches.len();
+        for m in matches {
+            self.matches += m.found.max(1);
+        }
+    }
+
+    // to_json is the stats as the search server reports them, with times in
+    // milliseconds.
+    pub fn to_json(&self) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        json!({
+            "files_searched": self.files_searched,
+            "files_skipped": {
+                "binary": self.files_binary,
+                "filtered": self.files_filtered,
+                "errors": self.files_errored,
+            },
+            "bytes_read": self.bytes_read,
+            "lines_scanned": self.lines_scanned,
+            "matches": self.matches,
+            "matched_lines": self.matched_lines,
+            "elapsed_ms": {
+                "total": ms(self.elapsed()),
+                "walk": ms(self.walk),
+                "read": ms(self.read),
+                "match": ms(self.search),
+            },
+        })
+    }
+}
+
+// Stats print as the summary --stats adds after the results:
+//
+//     3 files searched
+//     2 files skipped (1 binary, 1 filtered, 0 unreadable)
+//     ...
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = |d: Duration| format!("{:.3}s", d.as_secs_f64());
+        writeln!(f, "{} files searched", self.files_searched)?;
+        writeln!(
+            f,
+            "{} files skipped ({} binary, {} filtered, {} unreadable)",
+            self.files_skipped(), self.files_binary, self.files_filtered, self.files_errored
+        )?;
+        writeln!(f, "{} bytes read", self.bytes_read)?;
+        writeln!(f, "{} lines scanned", self.lines_scanned)?;
+        writeln!(f, "{} matches on {} lines", self.matches, self.matched_lines)?;
+        write!(
+            f,
+            "{} elapsed (walk {}, read {}, match {})",
+            secs(self.elapsed()), secs(self.walk), secs(self.read), secs(self.search)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_matches() {
+        let mut stats = Stats::default();
+        stats.count(&[
+            Match { line_number: 1, line: "ab AB ab", distance: None, found: 3 },
+            Match { line_number: 2, line: "a b", distance: Some(1), found: 1 },
+            Match { line_number: 3, line: "", distance: None, found: 0 },
+        ]);
+        assert_eq!(3, stats.matched_lines);
+        assert_eq!(5, stats.matches);
+    }
+
+    #[test]
+    fn summary() {
+        let stats = Stats { files_searched: 3, files_binary: 1, files_filtered: 1, matches: 4, matched_lines: 2, ..Default::default() };
+        let text = stats.to_string();
+        assert!(text.starts_with("3 files searched\n2 files skipped (1 binary, 1 filtered, 0 unreadable)\n"));
+        assert!(text.contains("4 matches on 2 lines\n"));
+        assert!(text.ends_with("0.000s elapsed (walk 0.000s, read 0.000s, match 0.000s)"));
+        assert_eq!(json!(1), stats.to_json()["files_skipped"]["binary"]);
+    }
+}
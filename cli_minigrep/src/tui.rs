@@ -0,0 +1,296 @@
+use std::env;
+use std::error::Error;
+use std::io::{self, Write};
+use std::process::Command;
+use crossterm::{cursor, event, terminal};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::{execute, queue};
+use {inputs, paths, shell, Config, Input};
+
+// run shows an interactive search over the configured inputs until the user
+// quits with Esc or Ctrl-C.
+pub fn run(cfg: Config) -> Result<(), Box<dyn Error>> {
+    let inputs = inputs(&cfg)?;
+    let mut app = App::new(cfg, inputs);
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = event_loop(&mut app, &mut out);
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn event_loop(app: &mut App, out: &mut io::Stdout) -> Result<(), Box<dyn Error>> {
+    loop {
+        let (width, height) = terminal::size()?;
+        for (ii, row) in app.render(width as usize, height as usize).iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, ii as u16), Print(row))?;
+        }
+        out.flush()?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Up => app.select(-1),
+            KeyCode::Down => app.select(1),
+            KeyCode::PageUp => app.select(-10),
+            KeyCode::PageDown => app.select(10),
+            KeyCode::Backspace => app.pop(),
+            KeyCode::Char(c) => app.push(c),
+            KeyCode::Enter => {
+                if let Some((path, line)) = app.target() {
+                    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+                    terminal::disable_raw_mode()?;
+                    let status = open_editor(&path, line);
+                    terminal::enable_raw_mode()?;
+                    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+                    app.message = match status {
+                        Ok(_) => None,
+                        Err(err) => Some(format!("could not open editor: {}", err)),
+                    };
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+// open_editor opens path at line in $VISUAL or $EDITOR.
+fn open_editor(path: &str, line: usize) -> Result<(), Box<dyn Error>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let status = match editor_command(&editor, path, line) {
+        Some(mut cmd) => cmd.status()?,
+        None => return Err("$EDITOR is empty".into()),
+    };
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status).into());
+    }
+    Ok(())
+}
+
+// editor_command is the command to open path at line in editor, a command
+// line that's run by sh if it needs to be. Most editors (vi, emacs, nano,
+// kak) understand "+LINE" as "start on this line".
+fn editor_command(editor: &str, path: &str, line: usize) -> Option<Command> {
+    shell(editor, &[format!("+{}", line), path.to_string()])
+}
+
+// Hit is one entry in the list of results.
+struct Hit {
+    input: usize,
+    line_number: usize,
+    line: String,
+}
+
+// App holds the state of the interactive search, independent of the terminal
+// it's drawn on.
+pub struct App {
+    cfg: Config,
+    inputs: Vec<Input>,
+    hits: Vec<Hit>,
+    selected: usize,
+    message: Option<String>,
+}
+
+impl App {
+    pub fn new(cfg: Config, inputs: Vec<Input>) -> App {
+        let mut app = App { cfg, inputs, hits: Vec::new(), selected: 0, message: None };
+        app.refresh();
+        app
+    }
+
+    // push adds c to the end of the query.
+    pub fn push(&mut self, c: char) {
+        self.cfg.query.push(c);
+        self.refresh();
+    }
+
+    // pop removes the last char of the query.
+    pub fn pop(&mut self) {
+        self.cfg.query.pop();
+        self.refresh();
+    }
+
+    // select moves the selection by delta entries, stopping at either end.
+    pub fn select(&mut self, delta: isize) {
+        if self.hits.is_empty() {
+            return;
+        }
+        let last = self.hits.len() - 1;
+        let next = self.selected as isize + delta;
+        self.selected = next.max(0).min(last as isize) as usize;
+    }
+
+    // target is the file and line of the selected hit, if it can be opened in
+    // an editor. Archive members can't be.
+    fn target(&mut self) -> Option<(String, usize)> {
+        let hit = self.hits.get(self.selected)?;
+        let input = &self.inputs[hit.input];
+        if input.member.is_some() {
+            self.message = Some("archive members can't be opened in an editor".to_string());
+            return None;
+        }
+        Some((input.path.clone(), hit.line_number))
+    }
+
+    fn refresh(&mut self) {
+        self.hits.clear();
+        self.selected = 0;
+        for (ii, input) in self.inputs.iter().enumerate() {
+            for m in input.search(&self.cfg) {
+                self.hits.push(Hit { input: ii, line_number: m.line_number, line: m.line.to_string() });
+            }
+        }
+    }
+
+    // render lays the app out as rows of exactly width chars: the query on
+    // top, the results on the left, a preview of the selected hit on the right
+    // and a status line at the bottom. The selected result and its line in
+    // the preview are marked with '>'.
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let body = height.saturating_sub(2);
+        let left = width * 2 / 5;
+        let right = width.saturating_sub(left + 3);
+        let scroll = (self.selected + 1).saturating_sub(body);
+
+        let mut list = Vec::with_capacity(body);
+        for (ii, hit) in self.hits.iter().enumerate().skip(scroll).take(body) {
+            let marker = if ii == self.selected { '>' } else { ' ' };
+            list.push(format!("{}{}:{}: {}", marker, paths::label(&self.cfg, &self.inputs[hit.input]), hit.line_number, hit.line));
+        }
+        let preview = self.preview(body);
+
+        let mut rows = Vec::with_capacity(height);
+        rows.push(fit(&format!("Query: {}", self.cfg.query), width));
+        for ii in 0..body {
+            let l = list.get(ii).map_or("", |s| s.as_str());
+            let r = preview.get(ii).map_or("", |s| s.as_str());
+            rows.push(format!("{} │ {}", fit(l, left), fit(r, right)));
+        }
+        let status = match self.message {
+            Some(ref message) => message.clone(),
+            None => format!("{} matches  ↑↓ select  Enter open in $EDITOR  Esc quit", self.hits.len()),
+        };
+        rows.push(fit(&status, width));
+        rows.truncate(height);
+        rows
+    }
+
+    // preview is the lines of the selected hit's input around the hit.
+    fn preview(&self, height: usize) -> Vec<String> {
+        let hit = match self.hits.get(self.selected) {
+            Some(hit) => hit,
+            None => return Vec::new(),
+        };
+        let input = &self.inputs[hit.input];
+        let first = (hit.line_number - input.first_line - 1).saturating_sub(height / 2);
+        input.contents.lines()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(ii, line)| {
+                let n = input.first_line + ii + 1;
+                let marker = if n == hit.line_number { '>' } else { ' ' };
+                format!("{}{:>5} {}", marker, n, line)
+            })
+            .collect()
+    }
+}
+
+// fit pads or cuts s to exactly width chars, turning tabs into spaces so they
+// can't throw out the layout.
+fn fit(s: &str, width: usize) -> String {
+    let mut out: String = s.chars()
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .take(width)
+        .collect();
+    let len = out.chars().count();
+    out.extend(std::iter::repeat_n(' ', width - len));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(query: &str) -> App {
+        let cfg = Config { query: query.to_string(), ..Default::default() };
+        let input = Input {
+            path: "poem.txt".to_string(),
+            member: None,
+            contents: "Rust:\nsafe, fast, productive.\nPick three.\nTrust me.".to_string(),
+            first_line: 0,
+        };
+        App::new(cfg, vec![input])
+    }
+
+    #[test]
+    fn refines_as_you_type() {
+        let mut app = app("r");
+        assert_eq!(4, app.hits.len());
+        app.push('u');
+        assert_eq!(2, app.hits.len());
+        app.push('x');
+        assert_eq!(0, app.hits.len());
+        app.pop();
+        app.pop();
+        assert_eq!(4, app.hits.len());
+    }
+
+    #[test]
+    fn selection_stays_in_bounds() {
+        let mut app = app("rust");
+        app.select(-1);
+        assert_eq!(0, app.selected);
+        app.select(10);
+        assert_eq!(1, app.selected);
+        assert_eq!(Some(("poem.txt".to_string(), 4)), app.target());
+    }
+
+    #[test]
+    fn renders_list_and_preview() {
+        let mut app = app("three");
+        let rows = app.render(60, 5);
+        assert_eq!(5, rows.len());
+        assert!(rows.iter().all(|row| row.chars().count() == 60));
+        assert!(rows[0].starts_with("Query: three"));
+        assert!(rows[1].starts_with(">poem.txt:3: Pick three."));
+        assert!(rows[1].contains("│ "));
+        assert!(rows.iter().any(|row| row.contains(">    3 Pick three.")));
+        assert!(rows[4].starts_with("1 matches"));
+
+        app.push('x');
+        let rows = app.render(60, 5);
+        assert!(rows[4].starts_with("0 matches"));
+    }
+
+    #[test]
+    fn labels_like_the_other_output() {
+        let mut app = app("three");
+        app.cfg.relative_to = Some("..".to_string());
+        app.cfg.path_separator = Some("|".to_string());
+        let cwd = env::current_dir().unwrap();
+        let dir = cwd.file_name().unwrap().to_string_lossy();
+        assert!(app.render(120, 5)[1].starts_with(&format!(">{}|poem.txt:3: ", dir)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn opens_quoted_editors() {
+        let run = |editor: &str| {
+            let output = editor_command(editor, "my notes.txt", 3).unwrap().output().unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_eq!("+3:my notes.txt", run("printf %s:%s"));
+        assert_eq!("[wait] +3:my notes.txt", run("printf '[%s] %s:%s' wait"));
+        assert!(editor_command("", "my notes.txt", 3).is_none());
+    }
+}
@@ -0,0 +1,412 @@
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Seek, SeekFrom};
+use std::time::{SystemTime, UNIX_EPOCH};
+use aggregate::MONTHS;
+use filter::{days_from_civil, Time};
+use range::{self, Range, Section};
+use {Config, Match};
+
+// Format says how to read the timestamp at the start of a line, strftime
+// style: %Y year, %m month, %d day, %e day padded with a space, %b month name
+// (Jan), %H hour, %M minute, %S second, %s seconds since 1970, %z zone (Z,
+// +02:00 or +0200) and %% for a '%'. Seconds can have a fraction, which is
+// ignored, and a zone can follow the timestamp without a %z. Times without a
+// zone are taken as UTC, and without a year as this year.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Format(Vec<String>);
+
+// AUTO are the formats tried when none is given: ISO 8601, with a 'T' or a
+// space, and syslog.
+const AUTO: [&str; 3] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%b %e %H:%M:%S"];
+
+impl Format {
+    // "[%d/%b/%Y:%H:%M:%S %z]" -> Format(["[%d/%b/%Y:%H:%M:%S %z]"])
+    pub fn parse(s: &str) -> Result<Format, String> {
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some('Y') | Some('m') | Some('d') | Some('e') | Some('b') | Some('H') | Some('M') | Some('S')
+                    | Some('s') | Some('z') | Some('%') => {},
+                Some(other) => return Err(format!("time format {} has an unknown field %{}", s, other)),
+                None => return Err(format!("time format {} ends with a lone %", s)),
+            }
+        }
+        Ok(Format(vec![s.to_string()]))
+    }
+
+    pub fn auto() -> Format {
+        Format(AUTO.iter().map(|f| f.to_string()).collect())
+    }
+
+    // stamp reads the timestamp at the start of line as seconds since 1970,
+    // filling in year if the format hasn't got one.
+    fn stamp(&self, line: &[u8], year: i64) -> Option<i64> {
+        self.0.iter().find_map(|format| stamp(format, line, year))
+    }
+}
+
+impl TryFrom<String> for Format {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Format, String> {
+        Format::parse(&s)
+    }
+}
+
+fn stamp(format: &str, b: &[u8], year: i64) -> Option<i64> {
+    let (mut y, mut mo, mut d, mut h, mut mi, mut s) = (year, 1, 1, 0, 0, 0);
+    let mut epoch = None;
+    let mut offset = None;
+    let mut pos = 0;
+    let mut fmt = format.bytes();
+    while let Some(c) = fmt.next() {
+        if c != b'%' {
+            if b.get(pos) != Some(&c) {
+                return None;
+            }
+            pos += 1;
+            continue;
+        }
+        match fmt.next()? {
+            b'Y' => y = field(b, &mut pos, 4, 4)?,
+            b'm' => mo = field(b, &mut pos, 2, 2)?,
+            b'd' => d = field(b, &mut pos, 2, 2)?,
+            b'e' => {
+                if b.get(pos) == Some(&b' ') {
+                    pos += 1;
+                }
+                d = field(b, &mut pos, 1, 2)?;
+            },
+            b'b' => {
+                mo = MONTHS.iter().position(|m| b[pos..].starts_with(m.as_bytes()))? as i64 + 1;
+                pos += 3;
+            },
+            b'H' => h = field(b, &mut pos, 2, 2)?,
+            b'M' => mi = field(b, &mut pos, 2, 2)?,
+            b'S' => {
+                s = field(b, &mut pos, 2, 2)?;
+                if b.get(pos) == Some(&b'.') {
+                    pos += 1;
+                    field(b, &mut pos, 1, 9)?;
+                }
+            },
+            b's' => epoch = Some(field(b, &mut pos, 1, 18)?),
+            b'z' => offset = Some(zone(b, &mut pos)?),
+            b'%' => {
+                if b.get(pos) != Some(&b'%') {
+                    return None;
+                }
+                pos += 1;
+            },
+            _ => return None,
+        }
+    }
+    if let Some(secs) = epoch {
+        return Some(secs);
+    }
+    let offset = offset.or_else(|| zone(b, &mut pos)).unwrap_or(0);
+    if !(1..=12).contains(&mo) || !(1..=31).contains(&d) || h > 23 || mi > 59 || s > 60 {
+        return None;
+    }
+    Some(days_from_civil(y, mo, d) * 24 * 60 * 60 + h * 60 * 60 + mi * 60 + s - offset)
+}
+
+// field reads a number of min to max digits at pos, moving pos past it.
+fn field(b: &[u8], pos: &mut usize, min: usize, max: usize) -> Option<i64> {
+    let n = b[*pos..].iter().take(max).take_while(|c| c.is_ascii_digit()).count();
+    if n < min {
+        return None;
+    }
+    let value = b[*pos..*pos + n].iter().fold(0, |v, &c| v * 10 + (c - b'0') as i64);
+    *pos += n;
+    Some(value)
+}
+
+// zone reads a zone at pos as seconds east of UTC: "Z", "+02:00", "-0530".
+fn zone(b: &[u8], pos: &mut usize) -> Option<i64> {
+    let sign = match b.get(*pos) {
+        Some(b'Z') => {
+            *pos += 1;
+            return Some(0);
+        },
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return None,
+    };
+    let mut at = *pos + 1;
+    let hours = field(b, &mut at, 2, 2)?;
+    if b.get(at) == Some(&b':') {
+        at += 1;
+    }
+    let minutes = field(b, &mut at, 2, 2)?;
+    *pos = at;
+    Some(sign * (hours * 60 + minutes) * 60)
+}
+
+// Window is the span of time --since and --until pick out. It includes its
+// start and stops just before its end, so --since 2026-10-01 --until
+// 2026-10-02 is the whole of the first of October.
+pub struct Window {
+    since: Option<i64>,
+    until: Option<i64>,
+    format: Format,
+    now: i64,
+    year: i64,
+}
+
+impl Window {
+    pub fn new(cfg: &Config) -> Option<Window> {
+        if cfg.since.is_none() && cfg.until.is_none() {
+            return None;
+        }
+        let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        let now = secs(SystemTime::now());
+        Some(Window {
+            since: cfg.since.map(|t: Time| secs(t.0)),
+            until: cfg.until.map(|t: Time| secs(t.0)),
+            format: cfg.time_format.clone().unwrap_or_else(Format::auto),
+            now,
+            year: year_of(now.div_euclid(24 * 60 * 60)),
+        })
+    }
+
+    // stamp reads the timestamp at the start of line. A timestamp without a
+    // year that would be in the future is taken to be from last year, so
+    // December's syslog still reads right in January.
+    fn stamp(&self, line: &[u8]) -> Option<i64> {
+        let t = self.format.stamp(line, self.year)?;
+        if t > self.now + 24 * 60 * 60 {
+            if let Some(earlier) = self.format.stamp(line, self.year - 1).filter(|&e| e < t) {
+                return Some(earlier);
+            }
+        }
+        Some(t)
+    }
+
+    fn contains(&self, t: i64) -> bool {
+        self.since.is_none_or(|since| t >= since) && self.until.is_none_or(|until| t < until)
+    }
+
+    // keep drops the matches on lines outside the window. A line without a
+    // timestamp of its own, like the rest of a stack trace, has the one of
+    // the line before it; lines before the first timestamp are dropped.
+    pub fn keep<'a>(&self, contents: &str, matches: Vec<Match<'a>>) -> Vec<Match<'a>> {
+        let mut lines = contents.lines().enumerate();
+        let mut current = None;
+        matches.into_iter().filter(|m| {
+            for (ii, line) in &mut lines {
+                if let Some(t) = self.stamp(line.as_bytes()) {
+                    current = Some(t);
+                }
+                if ii + 1 == m.line_number {
+                    break;
+                }
+            }
+            current.is_some_and(|t| self.contains(t))
+        }).collect()
+    }
+}
+
+// SCAN is how close the binary search gets before reading line by line.
+const SCAN: u64 = 64 * 1024;
+
+// read reads the part of r that can hold lines in the window, for
+// --sorted-times. Supposing the timestamps are in order, it binary searches
+// for where the window starts and ends, seeking about the file, so only a
+// few blocks outside the window are read. Every timestamp it lands on is
+// kept, and if they turn out not to be in order the whole of r is read
+// instead, but a line out of order that it never lands on goes unnoticed, so
+// in-window lines on the wrong side of it can be missed. Either way, keep
+// still checks each match.
+pub fn read<R: BufRead + Seek>(cfg: &Config, window: &Window, mut r: R) -> io::Result<Section> {
+    let len = r.seek(SeekFrom::End(0))?;
+    let mut probes = Vec::new();
+    scan(&mut r, 0, window, |_| true, &mut probes)?;
+    let start = match window.since {
+        Some(since) => first_where(&mut r, len, window, |t| t >= since, &mut probes)?,
+        None => 0,
+    };
+    let end = match window.until {
+        Some(until) => Some(first_where(&mut r, len, window, |t| t >= until, &mut probes)?),
+        None => None,
+    };
+    probes.sort_unstable();
+    let ordered = probes.windows(2).all(|p| p[0].1 <= p[1].1) && end.is_none_or(|end| start <= end);
+    let range = match ordered {
+        true => Range { start: Some(start), end },
+        false => Range { start: None, end: None },
+    };
+    r.seek(SeekFrom::Start(0))?;
+    range::read_bytes(r, &range, cfg.needs_line_numbers())
+}
+
+// first_where finds where the first line with a timestamp passing pred
+// starts, or the end of r if there isn't one, supposing the timestamps are
+// in order.
+fn first_where<R, F>(r: &mut R, len: u64, window: &Window, pred: F, probes: &mut Vec<(u64, i64)>) -> io::Result<u64>
+    where R: BufRead + Seek, F: Fn(i64) -> bool,
+{
+    // Every timestamp before lo fails pred.
+    let mut lo = 0;
+    let mut hi = len;
+    while hi - lo > SCAN {
+        let mid = lo + (hi - lo) / 2;
+        match scan(r, mid, window, |_| true, probes)? {
+            Some((at, t)) if at < hi && !pred(t) => lo = at,
+            _ => hi = mid,
+        }
+    }
+    Ok(scan(r, lo, window, pred, probes)?.map_or(len, |(at, _)| at))
+}
+
+// scan reads the lines of r from pos, skipping the rest of the line pos is
+// in unless it's 0, until one has a timestamp passing pred. It returns where
+// that line starts and its timestamp, and notes every timestamp it reads in
+// probes.
+fn scan<R, F>(r: &mut R, pos: u64, window: &Window, pred: F, probes: &mut Vec<(u64, i64)>) -> io::Result<Option<(u64, i64)>>
+    where R: BufRead + Seek, F: Fn(i64) -> bool,
+{
+    r.seek(SeekFrom::Start(pos))?;
+    let mut at = pos;
+    let mut buf = Vec::new();
+    if pos > 0 {
+        at += r.read_until(b'\n', &mut buf)? as u64;
+    }
+    loop {
+        buf.clear();
+        let n = r.read_until(b'\n', &mut buf)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if let Some(t) = window.stamp(&buf) {
+            probes.push((at, t));
+            if pred(t) {
+                return Ok(Some((at, t)));
+            }
+        }
+        at += n as u64;
+    }
+}
+
+// year_of finds the year of a day counted from 1970-01-01, undoing
+// days_from_civil.
+fn year_of(days: i64) -> i64 {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    era * 400 + yoe + (mp >= 10) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn window(since: Option<&str>, until: Option<&str>) -> Window {
+        let cfg = Config {
+            since: since.map(|s| Time::parse(s).unwrap()),
+            until: until.map(|s| Time::parse(s).unwrap()),
+            ..Default::default()
+        };
+        Window::new(&cfg).unwrap()
+    }
+
+    // log is a day of one line a second from midnight, with a line without a
+    // timestamp after every hundredth.
+    fn log() -> String {
+        let mut log = String::new();
+        for s in 0..24 * 60 * 60 {
+            log += &format!("2026-10-01T{:02}:{:02}:{:02}Z request {}\n", s / 3600, s / 60 % 60, s % 60, s);
+            if s % 100 == 0 {
+                log += "  at handler\n";
+            }
+        }
+        log
+    }
+
+    #[test]
+    fn reads_timestamps() {
+        let auto = Format::auto();
+        let at = |s: &str| Time::parse(s).unwrap().0.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        assert_eq!(Some(at("2026-10-01T13:30:00")), auto.stamp(b"2026-10-01T13:30:00.250Z GET /", 2026));
+        assert_eq!(Some(at("2026-10-01T11:30:00")), auto.stamp(b"2026-10-01 13:30:00+02:00 GET /", 2026));
+        assert_eq!(Some(at("2026-10-01T13:30:00")), auto.stamp(b"Oct  1 13:30:00 host sshd[1]: ok", 2026));
+        assert_eq!(None, auto.stamp(b"  at handler", 2026));
+
+        let clf = Format::parse("[%d/%b/%Y:%H:%M:%S %z]").unwrap();
+        assert_eq!(Some(at("2026-10-01T13:30:00")), clf.stamp(b"[01/Oct/2026:08:30:00 -0500] \"GET /\"", 2026));
+        assert_eq!(Some(1790000000), Format::parse("%s").unwrap().stamp(b"1790000000 ok", 2026));
+        assert!(Format::parse("%Y-%q").is_err());
+        assert!(Format::parse("100%").is_err());
+        assert_eq!(2026, year_of(at("2026-12-31T23:59:59") / 86400));
+        assert_eq!(2027, year_of(at("2027-01-01") / 86400));
+    }
+
+    #[test]
+    fn keeps_matches_in_window() {
+        let contents = "2026-10-01T09:00:00Z a\n  a continued\n2026-10-01T10:00:00Z a\n2026-10-01T11:00:00Z a\n";
+        let cfg = Config { query: "a".to_string(), ..Default::default() };
+        let matches = ::search_lines(&cfg, contents);
+        let kept = window(Some("2026-10-01T09:30"), Some("2026-10-01T11:00")).keep(contents, matches);
+        assert_eq!(vec![3], kept.iter().map(|m| m.line_number).collect::<Vec<_>>());
+
+        let matches = ::search_lines(&cfg, contents);
+        let kept = window(None, Some("2026-10-01T10:00")).keep(contents, matches);
+        assert_eq!(vec![1, 2], kept.iter().map(|m| m.line_number).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn binary_searches_ordered_logs() {
+        let log = log();
+        let cfg = Config { line_number: true, ..Default::default() };
+        let w = window(Some("2026-10-01T12:00:00"), Some("2026-10-01T12:00:10"));
+        let section = read(&cfg, &w, Cursor::new(log.as_bytes())).unwrap();
+        assert!(section.contents.starts_with("2026-10-01T12:00:00Z request 43200\n  at handler\n"));
+        assert!(section.contents.ends_with("2026-10-01T12:00:09Z request 43209\n"));
+        assert_eq!(log[..log.find("T12:00:00Z").unwrap()].matches('\n').count(), section.first_line);
+
+        let w = window(Some("2026-10-02"), None);
+        assert_eq!("", read(&cfg, &w, Cursor::new(log.as_bytes())).unwrap().contents);
+    }
+
+    #[test]
+    fn reads_everything_unless_sorted() {
+        // One line from the window was written early, where no probe lands.
+        let mut log = log();
+        let second = log.find('\n').unwrap() + 1;
+        log.insert_str(second, "2026-10-01T12:00:05Z late request\n");
+        let mut cfg = Config {
+            query: "late".to_string(),
+            since: Some(Time::parse("2026-10-01T12:00:00").unwrap()),
+            until: Some(Time::parse("2026-10-01T12:00:10").unwrap()),
+            ..Default::default()
+        };
+        let found = |cfg: &Config| {
+            let section = ::restrict(cfg, Cursor::new(log.as_bytes())).unwrap();
+            let input = ::Input { path: "app.log".to_string(), member: None, contents: section.contents, first_line: section.first_line };
+            input.search(cfg).iter().map(|m| m.line_number).collect::<Vec<_>>()
+        };
+        assert_eq!(vec![2], found(&cfg));
+        // Trusting the order, the binary search skips over it.
+        cfg.sorted_times = true;
+        assert!(found(&cfg).is_empty());
+    }
+
+    #[test]
+    fn reads_everything_out_of_order() {
+        let log = log();
+        let (morning, evening) = log.split_at(log.len() / 2);
+        let evening = &evening[evening.find('\n').unwrap() + 1..];
+        let log = format!("{}{}", evening, morning);
+        let w = window(Some("2026-10-01T12:00:00"), Some("2026-10-01T12:00:10"));
+        let section = read(&Config::default(), &w, Cursor::new(log.as_bytes())).unwrap();
+        assert_eq!(log.len(), section.contents.len());
+    }
+}
@@ -0,0 +1,237 @@
+// End to end tests: each one builds a directory tree, runs the minigrep
+// binary in it and compares the exit status, stdout and stderr with a golden
+// file in tests/golden. After a change to the output, rerun with
+// MINIGREP_BLESS=1 to write the new output to the golden files, and check the
+// diff before committing it.
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const POEM: &str = "\
+I’m nobody! Who are you?
+Are you nobody, too?
+Then there’s a pair of us — don’t tell!
+They’d banish us, you know.
+
+How dreary to be somebody!
+How public, like a frog
+To tell your name the livelong day
+To an admiring bog!
+";
+
+// Case is one run of the binary.
+#[derive(Default)]
+struct Case {
+    // files are created, with their directories, before the run.
+    files: &'static [(&'static str, &'static str)],
+    args: &'static [&'static str],
+    env: &'static [(&'static str, &'static str)],
+    stdin: Option<&'static str>,
+}
+
+// check runs case in a fresh directory and compares what it did with
+// tests/golden/<name>.txt.
+fn check(name: &str, case: Case) {
+    let dir = env::temp_dir().join(format!("minigrep-cli-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, contents) in case.files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+
+    // Keep the user's config and environment out of it.
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_minigrep"));
+    cmd.args(case.args)
+        .current_dir(&dir)
+        .env_remove("MATCH_CASE")
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", dir.join(".config"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for (key, value) in case.env {
+        cmd.env(key, value);
+    }
+    let mut child = cmd.spawn().unwrap();
+    child.stdin.take().unwrap().write_all(case.stdin.unwrap_or("").as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let tmp = dir.to_string_lossy().into_owned();
+    let actual = format!(
+        "status: {}\n--- stdout\n{}--- stderr\n{}",
+        output.status.code().map_or("signal".to_string(), |c| c.to_string()),
+        String::from_utf8_lossy(&output.stdout).replace(&tmp, "$TMP"),
+        String::from_utf8_lossy(&output.stderr).replace(&tmp, "$TMP"),
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let golden = golden(name);
+    if env::var_os("MINIGREP_BLESS").is_some() {
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|_| panic!("no {}; run with MINIGREP_BLESS=1 to write it", golden.display()));
+    assert!(expected == actual, "{} differs from the run:\n{}", golden.display(), actual);
+}
+
+fn golden(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.txt", name))
+}
+
+#[test]
+fn searches_a_file() {
+    check("searches_a_file", Case { files: &[("poem.txt", POEM)], args: &["to", "poem.txt"], ..Default::default() });
+}
+
+#[test]
+fn match_case_from_env() {
+    check("match_case_from_env", Case {
+        files: &[("poem.txt", POEM)],
+        args: &["To", "poem.txt"],
+        env: &[("MATCH_CASE", "1")],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn numbers_fuzzy_matches() {
+    check("numbers_fuzzy_matches", Case {
+        files: &[("poem.txt", POEM)],
+        args: &["-n", "--fuzzy=1", "frig", "poem.txt"],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn walks_a_tree() {
+    check("walks_a_tree", Case {
+        files: &[
+            ("src/main.rs", "fn main() {\n    // TODO: parse args\n}\n"),
+            ("src/lib/util.rs", "// todo later\npub fn util() {}\n"),
+            ("docs/notes.md", "nothing to see\n"),
+        ],
+        args: &["-n", "todo", "."],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn lists_files_with_matches() {
+    check("lists_files_with_matches", Case {
+        files: &[("a.txt", "needle\n"), ("b.txt", "hay\n"), ("c/d.txt", "more needle\n")],
+        args: &["-l", "--relative-to", "c", "needle", "."],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn reads_stdin() {
+    check("reads_stdin", Case { args: &["-n", "bog", "-"], stdin: Some(POEM), ..Default::default() });
+}
+
+#[test]
+fn labels_stdin_as_it_is() {
+    check("labels_stdin_as_it_is", Case {
+        args: &["-l", "--relative-to", "/usr", "--path-separator", "|", "bog", "-"],
+        stdin: Some(POEM),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn searches_stdin_for_serve() {
+    check("searches_stdin_for_serve", Case {
+        args: &["serve", "-"],
+        stdin: Some("observe\nignore\nServe it\n"),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn searches_a_file_for_serve() {
+    check("searches_a_file_for_serve", Case {
+        files: &[("notes.txt", "observe\nServe it\n")],
+        args: &["serve", "--match-case", "notes.txt"],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn reads_project_config() {
+    check("reads_project_config", Case {
+        files: &[("poem.txt", POEM), (".minigreprc", "# from the project\nmatch-case\nline-number\n")],
+        args: &["How", "poem.txt"],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn searches_csv_fields() {
+    check("searches_csv_fields", Case {
+        files: &[("users.csv", "name,city\nalice,paris\nbob,alice springs\n")],
+        args: &["--csv", "--field=name", "--header", "alice", "users.csv"],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn aggregates_log_lines() {
+    check("aggregates_log_lines", Case {
+        files: &[(
+            "app.log",
+            "2026-10-01T13:00:00Z timeout after 30ms\n\
+             2026-10-01T13:00:01Z ok\n\
+             2026-10-01T13:00:02Z timeout after 45ms\n\
+             2026-10-01T13:00:03Z timeout talking to 10.0.0.7\n",
+        )],
+        args: &["--aggregate", "--mask=all", "timeout", "app.log"],
+        ..Default::default()
+    });
+}
+
+#[test]
+fn reports_a_missing_query() {
+    check("reports_a_missing_query", Case { args: &[], ..Default::default() });
+}
+
+#[test]
+fn reports_a_missing_file() {
+    check("reports_a_missing_file", Case { args: &["to", "missing.txt"], ..Default::default() });
+}
+
+#[test]
+fn reports_bad_flags() {
+    check("reports_bad_flags", Case { args: &["--fuzzy=lots", "to", "poem.txt"], ..Default::default() });
+}
+
+// Closing the pipe early, as head or a pager the user quits does, ends the
+// search quietly instead of with a panic or an error.
+#[test]
+fn stops_when_the_reader_does() {
+    let dir = env::temp_dir().join(format!("minigrep-cli-{}-broken-pipe", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("big.txt"), "a line to match\n".repeat(200_000)).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["match", "big.txt"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", dir.join(".config"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = [0; 16];
+    child.stdout.take().unwrap().read_exact(&mut line).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(b"a line to match\n", &line);
+    assert!(output.stderr.is_empty(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+}